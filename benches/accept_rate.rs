@@ -0,0 +1,87 @@
+//! Measures how many connections per second the server accepts and answers
+//! during a reconnect storm, with one accept thread and with four
+//! SO_REUSEPORT acceptors sharing the port.
+//!
+//! Run with `cargo bench --bench accept_rate`.
+
+use embedded_recruitment_task::{
+    message::{client_message, AddRequest, ClientMessage, ServerMessage},
+    server::Server,
+};
+use prost::Message;
+use std::{
+    io::{Read, Write},
+    net::{SocketAddr, TcpStream},
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+const CLIENT_THREADS: usize = 16;
+const CONNECTIONS_PER_THREAD: usize = 250;
+
+/// Opens a connection, does one request/response round trip and closes it
+fn connect_once(addr: SocketAddr, request: &[u8]) {
+    let mut stream = TcpStream::connect(addr).expect("Failed to connect");
+    stream.write_all(request).expect("Failed to send request");
+
+    let mut buffer = [0u8; 64];
+    let bytes_read = stream.read(&mut buffer).expect("Failed to read response");
+    ServerMessage::decode(&buffer[..bytes_read]).expect("Failed to decode response");
+}
+
+fn accept_rate(acceptors: usize) -> f64 {
+    let server =
+        Arc::new(Server::new_reuseport("127.0.0.1:0", acceptors).expect("Failed to start server"));
+    let handle = {
+        let server = server.clone();
+        thread::spawn(move || server.run().expect("Server encountered an error"))
+    };
+    thread::sleep(Duration::from_millis(100));
+
+    let addr: SocketAddr = server.address().parse().unwrap();
+    let request = ClientMessage {
+        message: Some(client_message::Message::AddRequest(AddRequest {
+            a: 1,
+            b: 2,
+        })),
+        ..Default::default()
+    }
+    .encode_to_vec();
+
+    let start = Instant::now();
+    let clients: Vec<_> = (0..CLIENT_THREADS)
+        .map(|_| {
+            let request = request.clone();
+            thread::spawn(move || {
+                for _ in 0..CONNECTIONS_PER_THREAD {
+                    connect_once(addr, &request);
+                }
+            })
+        })
+        .collect();
+    for client in clients {
+        client.join().expect("Client thread panicked");
+    }
+    let elapsed = start.elapsed();
+
+    server.stop();
+    handle.join().expect("Server thread panicked");
+
+    (CLIENT_THREADS * CONNECTIONS_PER_THREAD) as f64 / elapsed.as_secs_f64()
+}
+
+fn main() {
+    println!(
+        "{} connections from {} client threads",
+        CLIENT_THREADS * CONNECTIONS_PER_THREAD,
+        CLIENT_THREADS
+    );
+    for acceptors in [1, 4] {
+        println!(
+            "acceptors = {}: {:>8.0} connections/s",
+            acceptors,
+            accept_rate(acceptors)
+        );
+    }
+}
@@ -1,7 +1,20 @@
 use std::error::Error;
 
 fn main() -> Result<(), Box<dyn Error>> {
-    prost_build::compile_protos(&["proto/messages.proto"], &["proto/"])?;
+    let mut config = prost_build::Config::new();
+
+    // JSON mapping for the HTTP gateway and the traffic recorder, only compiled
+    // in with the `json` feature
+    config.message_attribute(
+        ".",
+        "#[cfg_attr(feature = \"json\", derive(serde::Serialize, serde::Deserialize), serde(default))]",
+    );
+    config.enum_attribute(
+        ".",
+        "#[cfg_attr(feature = \"json\", derive(serde::Serialize, serde::Deserialize), serde(rename_all = \"snake_case\"))]",
+    );
+
+    config.compile_protos(&["proto/messages.proto"], &["proto/"])?;
 
     Ok(())
 }
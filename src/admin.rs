@@ -0,0 +1,225 @@
+//! Admin control channel.
+//!
+//! Operators connect to the admin listener (see `Server::with_admin`) and send
+//! one command per line; `help` lists them. Each answer is zero or more lines
+//! followed by `OK`, or by `ERR <reason>` when the command failed.
+//!
+//! The channel is not authenticated, so bind it where only operators can
+//! reach it, e.g. a Unix socket with mode `0o600`.
+
+use crate::audit::AuditEvent;
+use crate::connection::{ConnectionId, ConnectionSnapshot};
+use crate::listener::{Listener, Stream};
+use crate::server::Server;
+use log::LevelFilter;
+use std::{
+    io::{self, ErrorKind, Read, Write},
+    str::FromStr,
+    thread,
+    time::{Duration, UNIX_EPOCH},
+};
+use tracing::{error, info};
+
+/// How long a read waits before checking whether the server has stopped
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Longest command line accepted
+const MAX_LINE: usize = 1024;
+
+const HELP: &str = "\
+list                  open connections with their counters
+inspect <id>          details of one connection
+kick <id>             force-disconnect a connection
+drain                 stop accepting, serve open connections until they close
+filter                show the IP filter
+reload-filter         reload the IP filter from its file
+log-level [<level>]   show or set the log level (off, error, warn, info, debug, trace)
+help                  list the commands
+quit                  close the admin session";
+
+/// Accepts admin sessions one at a time for as long as `server` is up
+pub(crate) fn serve(server: &Server, listener: &Listener) {
+    while server.is_up() {
+        match listener.accept() {
+            Ok((stream, info)) => {
+                info!("Admin session opened by {}", info);
+                if let Err(e) = session(server, stream) {
+                    error!("Admin session error: {}", e);
+                }
+                info!("Admin session closed");
+            }
+            Err(ref e) if e.kind() == ErrorKind::WouldBlock => {
+                if let Err(e) = listener.wait_for_connection(POLL_INTERVAL) {
+                    error!("Error waiting for admin connections: {}", e);
+                    thread::sleep(POLL_INTERVAL);
+                }
+            }
+            Err(e) => error!("Error accepting admin connection: {}", e),
+        }
+    }
+}
+
+fn session(server: &Server, mut stream: Stream) -> io::Result<()> {
+    stream.set_read_timeout(Some(POLL_INTERVAL))?;
+    let mut pending = Vec::new();
+    let mut chunk = [0u8; 256];
+
+    loop {
+        while let Some(end) = pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = pending.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim();
+            if line == "quit" {
+                return Ok(());
+            }
+            if !line.is_empty() {
+                let reply = match execute(server, line) {
+                    Ok(output) => format!("{}OK\n", output),
+                    Err(reason) => format!("ERR {}\n", reason),
+                };
+                stream.write_all(reply.as_bytes())?;
+            }
+        }
+        if pending.len() > MAX_LINE {
+            stream.write_all(b"ERR line too long\n")?;
+            return Ok(());
+        }
+
+        match stream.read(&mut chunk) {
+            Ok(0) => return Ok(()),
+            Ok(n) => pending.extend_from_slice(&chunk[..n]),
+            Err(ref e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                if !server.is_up() {
+                    return Ok(());
+                }
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Runs one command, records it in the audit log and returns its output lines
+fn execute(server: &Server, line: &str) -> Result<String, String> {
+    let result = run(server, line);
+    server.audit(
+        None,
+        AuditEvent::AdminCommand {
+            command: line.to_string(),
+            error: result.as_ref().err().cloned(),
+        },
+    );
+    result
+}
+
+fn run(server: &Server, line: &str) -> Result<String, String> {
+    let mut words = line.split_whitespace();
+    let command = words.next().unwrap_or_default();
+    let args: Vec<&str> = words.collect();
+    info!("Admin command: {}", line);
+
+    match (command, args.as_slice()) {
+        ("list", []) => Ok(server
+            .connections()
+            .iter()
+            .map(|connection| format!("{}\n", summary(connection)))
+            .collect()),
+        ("inspect", [id]) => {
+            let id = parse_id(id)?;
+            server
+                .connection(id)
+                .map(|connection| details(&connection))
+                .ok_or_else(|| format!("no connection {}", id))
+        }
+        ("kick", [id]) => {
+            let id = parse_id(id)?;
+            server.disconnect(id).map_err(|e| e.to_string())?;
+            Ok(format!("disconnected {}\n", id))
+        }
+        ("drain", []) => {
+            server.drain();
+            Ok(format!(
+                "draining {} connection(s)\n",
+                server.connections().len()
+            ))
+        }
+        ("filter", []) => Ok(match server.ip_filter() {
+            Some(filter) => filter.to_string(),
+            None => "no filter, all addresses accepted\n".to_string(),
+        }),
+        ("reload-filter", []) => {
+            server.reload_ip_filter().map_err(|e| e.to_string())?;
+            Ok(server
+                .ip_filter()
+                .map(|filter| filter.to_string())
+                .unwrap_or_default())
+        }
+        ("log-level", []) => Ok(format!("{}\n", log::max_level())),
+        ("log-level", [level]) => {
+            let level = LevelFilter::from_str(level).map_err(|_| {
+                format!(
+                    "unknown level {:?}, expected off, error, warn, info, debug or trace",
+                    level
+                )
+            })?;
+            server.set_log_level(level).map_err(|e| e.to_string())?;
+            Ok(format!("{}\n", level))
+        }
+        ("help", []) => Ok(format!("{}\n", HELP)),
+        (
+            "list" | "inspect" | "kick" | "drain" | "filter" | "reload-filter" | "log-level"
+            | "help",
+            _,
+        ) => Err(format!("wrong arguments for {}, see help", command)),
+        _ => Err(format!("unknown command {:?}, see help", command)),
+    }
+}
+
+fn parse_id(id: &str) -> Result<ConnectionId, String> {
+    id.parse()
+        .map_err(|_| format!("invalid connection id {:?}", id))
+}
+
+fn summary(connection: &ConnectionSnapshot) -> String {
+    format!(
+        "{} {} connected={}s requests={} received={} sent={} decode_errors={}",
+        connection.id,
+        connection.info,
+        connection.connected_for.as_secs(),
+        connection.requests,
+        connection.bytes_received,
+        connection.bytes_sent,
+        connection.decode_errors
+    )
+}
+
+fn details(connection: &ConnectionSnapshot) -> String {
+    let connected_at = connection
+        .connected_at
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let mut out = format!("id: {}\npeer: {}\n", connection.id, connection.info.peer);
+    if let Some(identity) = &connection.identity {
+        out.push_str(&format!("identity: {}\n", identity));
+    }
+    if let Some(credentials) = connection.info.credentials {
+        out.push_str(&format!(
+            "credentials: uid={} gid={} pid={}\n",
+            credentials.uid,
+            credentials.gid,
+            credentials
+                .pid
+                .map_or_else(|| "unknown".to_string(), |pid| pid.to_string())
+        ));
+    }
+    out.push_str(&format!(
+        "connected_at: {}\nconnected_for: {:.3}s\nrequests: {}\ndecode_errors: {}\n\
+         bytes_received: {}\nbytes_sent: {}\n",
+        connected_at.as_secs(),
+        connection.connected_for.as_secs_f64(),
+        connection.requests,
+        connection.decode_errors,
+        connection.bytes_received,
+        connection.bytes_sent
+    ));
+    out
+}
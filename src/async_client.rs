@@ -0,0 +1,310 @@
+//! Tokio client multiplexing concurrent calls over one connection.
+//!
+//! ```no_run
+//! # async fn example() -> embedded_recruitment_task::client::Result<()> {
+//! use embedded_recruitment_task::async_client::AsyncClient;
+//! use std::time::Duration;
+//!
+//! // The server must be started with `Framing::LengthDelimited`
+//! let client = AsyncClient::connect("127.0.0.1:8080".parse().unwrap()).await?;
+//! let (echo, sum) = tokio::join!(client.echo("hello"), client.add(2, 3));
+//! assert_eq!((echo?, sum?), ("hello".to_string(), 5));
+//!
+//! // A shorter timeout for one call
+//! let quick = client.clone().with_timeout(Duration::from_millis(100));
+//! quick.echo("are you there?").await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! An `AsyncClient` is cheap to clone and every clone uses the same
+//! connection. Calls do not wait for each other: each request is written as
+//! soon as it is made, and responses are matched to their calls by request
+//! ID in whatever order they arrive. Requests written back to back must be
+//! told apart, so the server has to use `Framing::LengthDelimited` (see
+//! `Server::with_framing`).
+//!
+//! A call fails with `Error::Timeout` once its timeout passes. Dropping a
+//! call's future cancels it: a call that was not written yet never is, and
+//! the response to one already written is discarded.
+
+use crate::client::{Error, Result};
+use crate::framing::{self, Framing, MAX_FRAME_SIZE};
+use crate::message::{
+    client_message, server_message, AddRequest, ClientMessage, EchoMessage, ServerMessage,
+};
+use prost::Message;
+use std::{
+    collections::HashMap,
+    io::{self, ErrorKind},
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+        TcpStream,
+    },
+    sync::{mpsc, oneshot},
+    time,
+};
+use tracing::{debug, warn};
+
+/// How long `connect` waits, as long as the blocking client's default
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Requests queued for writing before further calls wait to be queued
+const QUEUE_SIZE: usize = 1024;
+
+/// Calls awaiting a response by request ID, `None` once the connection is
+/// closed
+type Pending = Mutex<Option<HashMap<u64, oneshot::Sender<ServerMessage>>>>;
+
+/// Async connection to a server, shared by its clones
+#[derive(Clone)]
+pub struct AsyncClient {
+    requests: mpsc::Sender<ClientMessage>,
+    pending: Arc<Pending>,
+    next_request_id: Arc<AtomicU64>,
+    /// How long a call may take, including waiting to be written
+    timeout: Duration,
+}
+
+impl AsyncClient {
+    /// Connects to the server at `addr` and spawns the tasks writing requests
+    /// and reading responses on the current Tokio runtime
+    pub async fn connect(addr: SocketAddr) -> Result<Self> {
+        Self::connect_timeout(addr, CONNECT_TIMEOUT).await
+    }
+
+    /// Like `connect`, giving up after `timeout`
+    pub async fn connect_timeout(addr: SocketAddr, timeout: Duration) -> Result<Self> {
+        let stream = time::timeout(timeout, TcpStream::connect(addr))
+            .await
+            .map_err(|_| io::Error::new(ErrorKind::TimedOut, "connection timed out"))
+            .and_then(|connected| connected)
+            .map_err(Error::Io)?;
+        stream.set_nodelay(true)?;
+        debug!("Connected to {}", addr);
+
+        let (reader, writer) = stream.into_split();
+        let pending = Arc::new(Mutex::new(Some(HashMap::new())));
+        let (requests, queue) = mpsc::channel(QUEUE_SIZE);
+        let (reading, read_ended) = oneshot::channel();
+        tokio::spawn(read_responses(reader, pending.clone(), reading));
+        tokio::spawn(write_requests(writer, queue, pending.clone(), read_ended));
+        Ok(AsyncClient {
+            requests,
+            pending,
+            next_request_id: Arc::new(AtomicU64::new(1)),
+            timeout: Duration::from_secs(30),
+        })
+    }
+
+    /// Sets how long each call may take
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Whether the connection is still open
+    pub fn is_connected(&self) -> bool {
+        self.pending.lock().unwrap().is_some()
+    }
+
+    /// Sends `content` to be echoed back and returns the echo
+    pub async fn echo(&self, content: &str) -> Result<String> {
+        let message = client_message::Message::EchoMessage(EchoMessage {
+            content: content.to_string(),
+        });
+        match self.request(message).await? {
+            server_message::Message::EchoMessage(echo) => Ok(echo.content),
+            other => Err(Error::UnexpectedResponse(Some(Box::new(other)))),
+        }
+    }
+
+    /// Has the server add `a` and `b`
+    pub async fn add(&self, a: i32, b: i32) -> Result<i32> {
+        let message = client_message::Message::AddRequest(AddRequest { a, b });
+        match self.request(message).await? {
+            server_message::Message::AddResponse(response) => Ok(response.result),
+            other => Err(Error::UnexpectedResponse(Some(Box::new(other)))),
+        }
+    }
+
+    /// Sends any request and waits for its response. `Error` responses are
+    /// returned as `Error::Server`.
+    pub async fn request(
+        &self,
+        message: client_message::Message,
+    ) -> Result<server_message::Message> {
+        let request_id = self.next_request_id.fetch_add(1, Ordering::Relaxed);
+        let (response, receiver) = oneshot::channel();
+        self.pending
+            .lock()
+            .unwrap()
+            .as_mut()
+            .ok_or(Error::Disconnected)?
+            .insert(request_id, response);
+        let _registered = Registered {
+            pending: &self.pending,
+            request_id,
+        };
+
+        let request = ClientMessage {
+            message: Some(message),
+            request_id,
+        };
+        let exchange = async {
+            self.requests
+                .send(request)
+                .await
+                .map_err(|_| Error::Disconnected)?;
+            // Calls awaiting a response are dropped when the connection ends
+            receiver.await.map_err(|_| Error::Disconnected)
+        };
+        let response = time::timeout(self.timeout, exchange)
+            .await
+            .map_err(|_| Error::Timeout)??;
+
+        match response.message {
+            Some(server_message::Message::Error(error)) => Err(Error::Server {
+                code: error.code(),
+                message: error.message,
+                field: error.field,
+            }),
+            Some(message) => Ok(message),
+            None => Err(Error::UnexpectedResponse(None)),
+        }
+    }
+}
+
+/// Unregisters a call however it ends, so that a cancelled call is not
+/// written and its late response is discarded
+struct Registered<'a> {
+    pending: &'a Pending,
+    request_id: u64,
+}
+
+impl Drop for Registered<'_> {
+    fn drop(&mut self) {
+        if let Some(pending) = self.pending.lock().unwrap().as_mut() {
+            pending.remove(&self.request_id);
+        }
+    }
+}
+
+/// Fails the calls awaiting a response and any made from now on
+fn close(pending: &Pending) {
+    pending.lock().unwrap().take();
+}
+
+/// Writes queued requests until the connection fails, the reader stops or
+/// every `AsyncClient` is dropped
+async fn write_requests(
+    mut writer: OwnedWriteHalf,
+    mut queue: mpsc::Receiver<ClientMessage>,
+    pending: Arc<Pending>,
+    mut read_ended: oneshot::Receiver<()>,
+) {
+    loop {
+        let request = tokio::select! {
+            request = queue.recv() => match request {
+                Some(request) => request,
+                None => break,
+            },
+            _ = &mut read_ended => break,
+        };
+        let request_id = request.request_id;
+        let registered = pending
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|pending| pending.contains_key(&request_id));
+        if !registered {
+            debug!("Dropping request {}, cancelled or timed out", request_id);
+            continue;
+        }
+        if let Err(e) = writer
+            .write_all(&Framing::LengthDelimited.encode(&request))
+            .await
+        {
+            warn!("Failed to send request {}: {}", request_id, e);
+            break;
+        }
+    }
+    close(&pending);
+    // Dropping the write half shuts it down, so the server closes the
+    // connection and the reader stops too
+}
+
+/// Hands responses to their calls until the connection closes. Dropping
+/// `_reading` on return stops the writer.
+async fn read_responses(
+    mut reader: OwnedReadHalf,
+    pending: Arc<Pending>,
+    _reading: oneshot::Sender<()>,
+) {
+    let mut buffer = vec![0; MAX_FRAME_SIZE];
+    // Received bytes not yet making up a whole frame
+    let mut received = Vec::new();
+    'connection: loop {
+        match reader.read(&mut buffer).await {
+            Ok(0) => break,
+            Ok(bytes_read) => received.extend_from_slice(&buffer[..bytes_read]),
+            Err(e) => {
+                warn!("Connection lost: {}", e);
+                break;
+            }
+        }
+
+        let mut consumed = 0;
+        loop {
+            match framing::next_frame(&received[consumed..]) {
+                Ok(Some(frame)) => {
+                    let message = &received[consumed + frame.start..consumed + frame.end];
+                    deliver(&pending, message);
+                    consumed += frame.end;
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    warn!("Closing connection after an invalid frame: {}", e);
+                    break 'connection;
+                }
+            }
+        }
+        received.drain(..consumed);
+    }
+    close(&pending);
+    debug!("Connection closed");
+}
+
+/// Hands a response to the call waiting for it, if any still is
+fn deliver(pending: &Pending, message: &[u8]) {
+    let response = match ServerMessage::decode(message) {
+        Ok(response) => response,
+        Err(e) => {
+            warn!("Skipping undecodable response: {}", e);
+            return;
+        }
+    };
+    let call = pending
+        .lock()
+        .unwrap()
+        .as_mut()
+        .and_then(|pending| pending.remove(&response.request_id));
+    match call {
+        Some(call) => {
+            let _ = call.send(response);
+        }
+        None => debug!(
+            "Discarding response to request {}, cancelled or timed out",
+            response.request_id
+        ),
+    }
+}
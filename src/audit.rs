@@ -0,0 +1,306 @@
+//! Tamper-evident audit log of security-relevant events.
+//!
+//! An `AuditLog` (see `Server::with_audit_log`) is a file of its own, apart
+//! from the `log` output, that entries are only ever appended to. Each line
+//! holds tab-separated fields:
+//!
+//! ```text
+//! <seq> <timestamp ms> <connection ID or -> <event> <details> <hash>
+//! ```
+//!
+//! where `hash` is the hex SHA-256 of the previous entry's hash followed by
+//! the line up to the hash. Changing, inserting or removing an entry breaks
+//! the chain from there on, which `verify` detects. Truncating the end of the
+//! log does not, so keep a copy of `AuditLog::head()` elsewhere to catch that.
+
+use crate::connection::ConnectionId;
+use sha2::{Digest, Sha256};
+use std::{
+    fmt,
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, ErrorKind, Write},
+    path::Path,
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tracing::error;
+
+/// Hash the first entry is chained to
+const GENESIS: [u8; 32] = [0; 32];
+
+/// Something worth auditing
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuditEvent {
+    AuthSuccess {
+        identity: String,
+    },
+    /// Rejected credentials; `identity` is the one claimed, if any
+    AuthFailure {
+        identity: String,
+    },
+    PermissionDenied {
+        identity: String,
+        message_type: String,
+    },
+    /// A command run on the admin channel, with its error if it failed
+    AdminCommand {
+        command: String,
+        error: Option<String>,
+    },
+    /// A connection closed by the server, e.g. kicked by an operator
+    ForcedDisconnect {
+        peer: String,
+    },
+    /// A connection refused by the IP filter
+    ConnectionRefused {
+        peer: String,
+    },
+    /// Entries of the `event` kind left out because too many occurred at once
+    Suppressed {
+        event: &'static str,
+        count: u64,
+    },
+}
+
+impl AuditEvent {
+    /// Name of the event in the log
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AuditEvent::AuthSuccess { .. } => "auth_success",
+            AuditEvent::AuthFailure { .. } => "auth_failure",
+            AuditEvent::PermissionDenied { .. } => "permission_denied",
+            AuditEvent::AdminCommand { .. } => "admin_command",
+            AuditEvent::ForcedDisconnect { .. } => "forced_disconnect",
+            AuditEvent::ConnectionRefused { .. } => "connection_refused",
+            AuditEvent::Suppressed { .. } => "suppressed",
+        }
+    }
+}
+
+/// Details as `key=value` pairs. Client-supplied strings are quoted and
+/// escaped so that they cannot forge fields or entries.
+impl fmt::Display for AuditEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuditEvent::AuthSuccess { identity } | AuditEvent::AuthFailure { identity } => {
+                write!(f, "identity={:?}", identity)
+            }
+            AuditEvent::PermissionDenied {
+                identity,
+                message_type,
+            } => write!(f, "identity={:?} message_type={:?}", identity, message_type),
+            AuditEvent::AdminCommand { command, error } => match error {
+                None => write!(f, "command={:?} result=ok", command),
+                Some(error) => write!(f, "command={:?} result=error error={:?}", command, error),
+            },
+            AuditEvent::ForcedDisconnect { peer } | AuditEvent::ConnectionRefused { peer } => {
+                write!(f, "peer={:?}", peer)
+            }
+            AuditEvent::Suppressed { event, count } => {
+                write!(f, "event={} count={}", event, count)
+            }
+        }
+    }
+}
+
+/// Position in the hash chain
+struct Chain {
+    file: File,
+    /// Sequence number of the next entry
+    seq: u64,
+    head: [u8; 32],
+}
+
+/// Append-only, hash-chained audit log file
+pub struct AuditLog {
+    chain: Mutex<Chain>,
+}
+
+impl AuditLog {
+    /// Opens the audit log at `path`, creating it if needed. An existing log
+    /// is verified first and extended; a broken one is refused.
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        let (seq, head) = match File::open(path) {
+            Ok(file) => read_chain(BufReader::new(file))?,
+            Err(e) if e.kind() == ErrorKind::NotFound => (0, GENESIS),
+            Err(e) => return Err(e),
+        };
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(AuditLog {
+            chain: Mutex::new(Chain { file, seq, head }),
+        })
+    }
+
+    /// Appends an entry and writes it through to the file
+    pub fn record(&self, connection: Option<ConnectionId>, event: &AuditEvent) -> io::Result<()> {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let connection = connection.map_or_else(|| "-".to_string(), |id| id.to_string());
+
+        let mut chain = self.chain.lock().unwrap();
+        let body = format!(
+            "{}\t{}\t{}\t{}\t{}",
+            chain.seq,
+            timestamp_ms,
+            connection,
+            event.kind(),
+            event
+        );
+        let hash = chain_hash(&chain.head, &body);
+        chain
+            .file
+            .write_all(format!("{}\t{}\n", body, to_hex(&hash)).as_bytes())?;
+        chain.file.flush()?;
+        chain.seq += 1;
+        chain.head = hash;
+        Ok(())
+    }
+
+    /// Hex hash of the last entry, which covers the whole log
+    pub fn head(&self) -> String {
+        to_hex(&self.chain.lock().unwrap().head)
+    }
+}
+
+/// Records `event` if there is an audit log. A failed write is logged but
+/// does not fail the operation being audited.
+pub(crate) fn record(
+    audit: Option<&AuditLog>,
+    connection: Option<ConnectionId>,
+    event: AuditEvent,
+) {
+    if let Some(audit) = audit {
+        if let Err(e) = audit.record(connection, &event) {
+            error!("Failed to write audit entry {}: {}", event.kind(), e);
+        }
+    }
+}
+
+/// Caps how many entries of a noisy event kind are written per interval, so
+/// that a flood does not stall its caller on audit writes. The entries over
+/// the cap are counted and written as one `Suppressed` entry once the
+/// interval has passed.
+pub(crate) struct Throttle {
+    event: &'static str,
+    limit: u32,
+    interval: Duration,
+    window: Mutex<Window>,
+}
+
+/// Entries of the current interval
+struct Window {
+    start: Instant,
+    written: u32,
+    suppressed: u64,
+}
+
+impl Window {
+    fn new() -> Self {
+        Window {
+            start: Instant::now(),
+            written: 0,
+            suppressed: 0,
+        }
+    }
+}
+
+impl Throttle {
+    /// Writes at most `limit` entries of the `event` kind per `interval`
+    pub(crate) fn new(event: &'static str, limit: u32, interval: Duration) -> Self {
+        Throttle {
+            event,
+            limit,
+            interval,
+            window: Mutex::new(Window::new()),
+        }
+    }
+
+    /// Records `event` like `record`, unless the limit for the current
+    /// interval has been reached
+    pub(crate) fn record(
+        &self,
+        audit: Option<&AuditLog>,
+        connection: Option<ConnectionId>,
+        event: AuditEvent,
+    ) {
+        if audit.is_none() {
+            return;
+        }
+        let mut window = self.window.lock().unwrap();
+        self.close_window(&mut window, audit, false);
+        if window.written < self.limit {
+            window.written += 1;
+            record(audit, connection, event);
+        } else {
+            window.suppressed += 1;
+        }
+    }
+
+    /// Writes the count of suppressed entries if the interval has passed, or
+    /// right away if `now` is set
+    pub(crate) fn flush(&self, audit: Option<&AuditLog>, now: bool) {
+        if audit.is_some() {
+            self.close_window(&mut self.window.lock().unwrap(), audit, now);
+        }
+    }
+
+    fn close_window(&self, window: &mut Window, audit: Option<&AuditLog>, now: bool) {
+        if !now && window.start.elapsed() < self.interval {
+            return;
+        }
+        if window.suppressed > 0 {
+            let event = AuditEvent::Suppressed {
+                event: self.event,
+                count: window.suppressed,
+            };
+            record(audit, None, event);
+        }
+        *window = Window::new();
+    }
+}
+
+/// Checks the hash chain of the audit log at `path` and returns the number
+/// of entries. Fails with `InvalidData` naming the first bad line.
+pub fn verify<P: AsRef<Path>>(path: P) -> io::Result<u64> {
+    read_chain(BufReader::new(File::open(path)?)).map(|(entries, _)| entries)
+}
+
+/// Follows the chain to its end, returning the entry count and head hash
+fn read_chain(reader: impl BufRead) -> io::Result<(u64, [u8; 32])> {
+    let mut seq = 0;
+    let mut head = GENESIS;
+    for (number, line) in reader.lines().enumerate() {
+        let line = line?;
+        let invalid = |reason: &str| {
+            io::Error::new(
+                ErrorKind::InvalidData,
+                format!("audit log line {}: {}", number + 1, reason),
+            )
+        };
+        let (body, hash) = line.rsplit_once('\t').ok_or_else(|| invalid("no hash"))?;
+        if !body.starts_with(&format!("{}\t", seq)) {
+            return Err(invalid("out of sequence"));
+        }
+        let expected = chain_hash(&head, body);
+        if hash != to_hex(&expected) {
+            return Err(invalid("hash mismatch"));
+        }
+        seq += 1;
+        head = expected;
+    }
+    Ok((seq, head))
+}
+
+fn chain_hash(previous: &[u8; 32], body: &str) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(previous);
+    hasher.update(body.as_bytes());
+    hasher.finalize().into()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,246 @@
+//! Connection authentication.
+//!
+//! A server with a `CredentialStore` (see `Server::with_credentials`) only
+//! handles a connection's requests once it has authenticated, in one of two
+//! ways:
+//!
+//! - bearer token: send `AuthRequest { token }`;
+//! - shared-secret challenge/response: send `AuthChallengeRequest`, then
+//!   `AuthRequest { identity, hmac }` where `hmac` is the HMAC-SHA256 of the
+//!   received nonce keyed with the identity's secret. Nonces are single use.
+//!
+//! Until then requests are answered with an `Unauthenticated` error. Health
+//! checks are exempt so that orchestrator probes need no credentials.
+
+use crate::audit::{self, AuditEvent, AuditLog};
+use crate::connection::Connection;
+use crate::handler::{error_response, ClientMessageWrapper, ServerMessageWrapper};
+use crate::message::{
+    auth_request::Credential, client_message, server_message, AuthChallenge, AuthRequest,
+    AuthResponse, ErrorCode,
+};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, ErrorKind},
+    path::Path,
+    sync::Arc,
+};
+use tracing::{info, warn};
+
+/// Length of challenge nonces, in bytes
+pub const NONCE_LEN: usize = 32;
+
+/// Tokens and shared secrets of the identities allowed to connect
+#[derive(Default)]
+pub struct CredentialStore {
+    /// `(identity, SHA-256 of the token)`, so tokens are not kept in memory
+    tokens: Vec<(String, [u8; 32])>,
+    secrets: HashMap<String, Vec<u8>>,
+}
+
+impl CredentialStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads a credential file with one credential per line:
+    ///
+    /// ```text
+    /// # comment
+    /// token <identity> <token>
+    /// secret <identity> <shared secret>
+    /// ```
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut store = Self::new();
+        for (number, line) in fs::read_to_string(path)?.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let words: Vec<&str> = line.split_whitespace().collect();
+            store = match words.as_slice() {
+                ["token", identity, token] => store.with_token(identity, token),
+                ["secret", identity, secret] => store.with_secret(identity, secret.as_bytes()),
+                _ => {
+                    return Err(io::Error::new(
+                        ErrorKind::InvalidData,
+                        format!(
+                            "line {}: expected `token <identity> <token>` or \
+                             `secret <identity> <secret>`",
+                            number + 1
+                        ),
+                    ))
+                }
+            };
+        }
+        Ok(store)
+    }
+
+    /// Lets `identity` authenticate with a bearer token
+    pub fn with_token(mut self, identity: &str, token: &str) -> Self {
+        self.tokens
+            .push((identity.to_string(), Sha256::digest(token).into()));
+        self
+    }
+
+    /// Lets `identity` authenticate by answering challenges with an HMAC
+    /// keyed with `secret`
+    pub fn with_secret(mut self, identity: &str, secret: impl Into<Vec<u8>>) -> Self {
+        self.secrets.insert(identity.to_string(), secret.into());
+        self
+    }
+
+    /// Returns the identity a bearer token belongs to
+    pub fn authenticate_token(&self, token: &str) -> Option<&str> {
+        let digest: [u8; 32] = Sha256::digest(token).into();
+        // Every entry is compared so that timing does not reveal which matched
+        let mut found = None;
+        for (identity, expected) in &self.tokens {
+            if constant_time_eq(expected, &digest) {
+                found = Some(identity.as_str());
+            }
+        }
+        found
+    }
+
+    /// Whether `mac` is the HMAC-SHA256 of `nonce` with `identity`'s secret
+    pub fn verify_hmac(&self, identity: &str, nonce: &[u8], mac: &[u8]) -> bool {
+        let Some(secret) = self.secrets.get(identity) else {
+            return false;
+        };
+        let mut expected =
+            Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+        expected.update(nonce);
+        expected.verify_slice(mac).is_ok()
+    }
+}
+
+/// Computes the HMAC a client answers a challenge with
+pub fn challenge_response(secret: &[u8], nonce: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(nonce);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Authentication state of one connection
+pub(crate) struct Session {
+    store: Arc<CredentialStore>,
+    /// Where authentication attempts are recorded, if anywhere
+    audit: Option<Arc<AuditLog>>,
+    /// Nonce of the outstanding challenge
+    nonce: Option<[u8; NONCE_LEN]>,
+}
+
+impl Session {
+    pub fn new(store: Arc<CredentialStore>, audit: Option<Arc<AuditLog>>) -> Self {
+        Session {
+            store,
+            audit,
+            nonce: None,
+        }
+    }
+
+    /// Answers authentication requests and rejects requests of connections
+    /// that have not authenticated. Returns `None` when the request may be
+    /// handled.
+    pub fn check(
+        &mut self,
+        request: &ClientMessageWrapper,
+        connection: &Connection,
+    ) -> Option<ServerMessageWrapper> {
+        let request_id = request.request_id;
+        match &request.message {
+            Some(client_message::Message::AuthChallengeRequest(_)) => {
+                let mut nonce = [0u8; NONCE_LEN];
+                if let Err(e) = getrandom::getrandom(&mut nonce) {
+                    warn!("Failed to generate a challenge nonce: {}", e);
+                    return Some(error_response(
+                        request_id,
+                        ErrorCode::Unspecified,
+                        "failed to generate a challenge",
+                    ));
+                }
+                self.nonce = Some(nonce);
+                Some(ServerMessageWrapper {
+                    message: Some(server_message::Message::AuthChallenge(AuthChallenge {
+                        nonce: nonce.to_vec(),
+                    })),
+                    request_id,
+                })
+            }
+            Some(client_message::Message::AuthRequest(auth)) => {
+                // A failed attempt also drops a previous authentication
+                connection.set_identity(None);
+                match self.authenticate(auth) {
+                    Some(identity) => {
+                        info!(
+                            "Connection {} authenticated as {}",
+                            connection.id(),
+                            identity
+                        );
+                        connection.set_identity(Some(identity.clone()));
+                        self.audit(
+                            connection,
+                            AuditEvent::AuthSuccess {
+                                identity: identity.clone(),
+                            },
+                        );
+                        Some(ServerMessageWrapper {
+                            message: Some(server_message::Message::AuthResponse(AuthResponse {
+                                identity,
+                            })),
+                            request_id,
+                        })
+                    }
+                    None => {
+                        warn!("Connection {} failed to authenticate", connection.id());
+                        self.audit(
+                            connection,
+                            AuditEvent::AuthFailure {
+                                identity: auth.identity.clone(),
+                            },
+                        );
+                        Some(error_response(
+                            request_id,
+                            ErrorCode::Unauthenticated,
+                            "invalid credentials",
+                        ))
+                    }
+                }
+            }
+            Some(client_message::Message::HealthCheckRequest(_)) => None,
+            _ if connection.identity().is_some() => None,
+            _ => Some(error_response(
+                request_id,
+                ErrorCode::Unauthenticated,
+                "authentication required",
+            )),
+        }
+    }
+
+    fn audit(&self, connection: &Connection, event: AuditEvent) {
+        audit::record(self.audit.as_deref(), Some(connection.id()), event);
+    }
+
+    fn authenticate(&mut self, auth: &AuthRequest) -> Option<String> {
+        match &auth.credential {
+            Some(Credential::Token(token)) => {
+                self.store.authenticate_token(token).map(str::to_string)
+            }
+            Some(Credential::Hmac(mac)) => {
+                let nonce = self.nonce.take()?;
+                self.store
+                    .verify_hmac(&auth.identity, &nonce, mac)
+                    .then(|| auth.identity.clone())
+            }
+            None => None,
+        }
+    }
+}
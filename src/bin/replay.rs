@@ -0,0 +1,52 @@
+//! Replays a traffic recording against a running server and prints every
+//! response that differs from the recorded one.
+//!
+//! Usage: `replay [--length-delimited] <recording.jsonl> <host:port>`
+//!
+//! `--length-delimited` talks to a server set up with
+//! `Framing::LengthDelimited`.
+
+use embedded_recruitment_task::{framing::Framing, recorder};
+use std::{env, process::ExitCode, time::Duration};
+
+/// How long to wait for each recorded response
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(2);
+
+fn main() -> ExitCode {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let framing = match args.iter().position(|arg| arg == "--length-delimited") {
+        Some(index) => {
+            args.remove(index);
+            Framing::LengthDelimited
+        }
+        None => Framing::Unframed,
+    };
+    let [recording, addr] = args.as_slice() else {
+        eprintln!("usage: replay [--length-delimited] <recording.jsonl> <host:port>");
+        return ExitCode::from(2);
+    };
+
+    let report = recorder::read_recording(recording)
+        .and_then(|records| recorder::replay(&records, addr, framing, RESPONSE_TIMEOUT));
+    let report = match report {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("replay failed: {}", e);
+            return ExitCode::from(2);
+        }
+    };
+
+    for mismatch in &report.mismatches {
+        println!("{}\n", mismatch);
+    }
+    println!(
+        "{} message(s) replayed, {} mismatch(es)",
+        report.replayed,
+        report.mismatches.len()
+    );
+    if report.mismatches.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
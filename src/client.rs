@@ -0,0 +1,603 @@
+//! Blocking client for the server's protocol.
+//!
+//! ```no_run
+//! use embedded_recruitment_task::client::Client;
+//!
+//! let mut client = Client::new("127.0.0.1:8080".parse().unwrap());
+//! client.connect()?;
+//! assert_eq!(client.echo("hello")?, "hello");
+//! assert_eq!(client.add(2, 3)?, 5);
+//! # Ok::<(), embedded_recruitment_task::client::Error>(())
+//! ```
+//!
+//! Every request carries a request ID, and responses to other IDs, e.g. late
+//! answers to requests that timed out, are skipped. A server set up with
+//! `Framing::LengthDelimited` needs `Client::with_framing` to match.
+//!
+//! With `Client::with_reconnect`, a client whose connection is lost, e.g.
+//! because the server restarted, reconnects on the next request, waiting
+//! with exponential backoff between failed attempts. Idempotent requests
+//! that were cut off are sent again on the new connection; authentication
+//! requests are not. Authenticate in `Client::on_connect` instead, which
+//! runs on every new connection before it is used:
+//!
+//! ```no_run
+//! use embedded_recruitment_task::client::{Client, ReconnectPolicy};
+//! use embedded_recruitment_task::message::{auth_request::Credential, client_message, AuthRequest};
+//!
+//! let mut client = Client::new("127.0.0.1:8080".parse().unwrap())
+//!     .with_reconnect(ReconnectPolicy::default())
+//!     .on_connect(|client| {
+//!         client.request(client_message::Message::AuthRequest(AuthRequest {
+//!             identity: String::new(),
+//!             credential: Some(Credential::Token("device-token".to_string())),
+//!         }))?;
+//!         Ok(())
+//!     });
+//! client.connect()?;
+//! # Ok::<(), embedded_recruitment_task::client::Error>(())
+//! ```
+
+use crate::framing::{self, Framing};
+use crate::message::{
+    client_message, server_message, AddRequest, ClientMessage, EchoMessage, ErrorCode,
+    ServerMessage,
+};
+#[cfg(feature = "tls")]
+use crate::tls::TlsClientConfig;
+use prost::Message;
+#[cfg(feature = "tls")]
+use std::sync::Arc;
+use std::{
+    fmt,
+    io::{self, ErrorKind, Read, Write},
+    net::{Shutdown, SocketAddr, TcpStream},
+    thread,
+    time::Duration,
+};
+use tracing::{debug, warn};
+
+/// Largest response the client reads, matching the server's read buffer
+const MAX_MESSAGE_SIZE: usize = 65536;
+
+/// Why a request failed
+#[derive(Debug)]
+pub enum Error {
+    /// Connecting, sending or receiving failed
+    Io(io::Error),
+    /// There is no connection; call `Client::connect` first
+    NotConnected,
+    /// The server closed the connection
+    Disconnected,
+    /// No response arrived within the request timeout
+    Timeout,
+    /// No pooled connection became available within the checkout timeout
+    CheckoutTimeout,
+    /// The server sent something that is not a `ServerMessage`
+    Decode(prost::DecodeError),
+    /// The server answered with an `Error`
+    Server {
+        code: ErrorCode,
+        message: String,
+        /// The offending request field, if the error names one
+        field: String,
+    },
+    /// The server answered with a response of the wrong type
+    UnexpectedResponse(Option<Box<server_message::Message>>),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "I/O error: {}", e),
+            Error::NotConnected => f.write_str("not connected"),
+            Error::Disconnected => f.write_str("server closed the connection"),
+            Error::Timeout => f.write_str("request timed out"),
+            Error::CheckoutTimeout => f.write_str("timed out waiting for a pooled connection"),
+            Error::Decode(e) => write!(f, "invalid response: {}", e),
+            Error::Server { code, message, .. } => {
+                write!(f, "server error {}: {}", code.as_str_name(), message)
+            }
+            Error::UnexpectedResponse(response) => write!(f, "unexpected response {:?}", response),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Decode(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            // Read and write timeouts surface as either, depending on the platform
+            ErrorKind::WouldBlock | ErrorKind::TimedOut => Error::Timeout,
+            ErrorKind::UnexpectedEof
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::BrokenPipe => Error::Disconnected,
+            _ => Error::Io(e),
+        }
+    }
+}
+
+impl From<prost::DecodeError> for Error {
+    fn from(e: prost::DecodeError) -> Self {
+        Error::Decode(e)
+    }
+}
+
+/// How a client reconnects after losing its connection
+#[derive(Debug, Clone)]
+pub struct ReconnectPolicy {
+    /// Delay before the first retry
+    pub initial_backoff: Duration,
+    /// Longest delay between retries
+    pub max_backoff: Duration,
+    /// Factor the delay grows by after each failed retry
+    pub multiplier: f64,
+    /// Fraction of each delay that is random, from 0.0 to 1.0, so that
+    /// clients cut off together do not reconnect in lockstep
+    pub jitter: f64,
+    /// Retries a request gets before giving up, counting both failed
+    /// connection attempts and resending it after the connection was lost;
+    /// `None` retries forever
+    pub max_retries: Option<u32>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        ReconnectPolicy {
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+            multiplier: 2.0,
+            jitter: 0.2,
+            max_retries: Some(5),
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Delay before retry number `retry`, counting from 0.
+    ///
+    /// Out of range settings are clamped rather than trusted: a multiplier
+    /// below 1.0 or NaN counts as 1.0, and a jitter outside 0.0 to 1.0 as the
+    /// nearest bound, or as 0.0 if NaN.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let multiplier = if self.multiplier.is_nan() {
+            1.0
+        } else {
+            self.multiplier.max(1.0)
+        };
+        let exponent = i32::try_from(retry).unwrap_or(i32::MAX);
+        // `min` also replaces the NaN of zero times an infinite growth
+        let delay = (self.initial_backoff.as_secs_f64() * multiplier.powi(exponent))
+            .min(self.max_backoff.as_secs_f64());
+        let jitter = if self.jitter.is_nan() {
+            0.0
+        } else {
+            self.jitter.clamp(0.0, 1.0)
+        };
+        // Rounding can push a delay near `Duration::MAX` past it
+        Duration::try_from_secs_f64(delay * (1.0 - jitter * random_fraction()))
+            .unwrap_or(self.max_backoff)
+    }
+
+    fn allows_retry(&self, retries: u32) -> bool {
+        self.max_retries.is_none_or(|max| retries < max)
+    }
+}
+
+/// Uniformly distributed in `[0, 1)`
+fn random_fraction() -> f64 {
+    let mut bytes = [0; 4];
+    // Without randomness the delays are merely not jittered
+    if getrandom::getrandom(&mut bytes).is_err() {
+        return 0.0;
+    }
+    f64::from(u32::from_le_bytes(bytes)) / (f64::from(u32::MAX) + 1.0)
+}
+
+/// Reported to the `Client::on_state_change` callback
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    Connected,
+    /// The connection was closed or lost
+    Disconnected,
+    /// Reconnect attempt number `attempt`, counting from 1, is starting
+    Reconnecting {
+        attempt: u32,
+    },
+}
+
+/// Whether `message` may be sent again when it is unknown whether the server
+/// handled it. Authentication is bound to the connection it happened on.
+fn is_idempotent(message: &client_message::Message) -> bool {
+    !matches!(
+        message,
+        client_message::Message::AuthChallengeRequest(_) | client_message::Message::AuthRequest(_)
+    )
+}
+
+/// Plain TCP or TLS connection to the server
+enum Stream {
+    Plain(TcpStream),
+    #[cfg(feature = "tls")]
+    Tls(Box<rustls::StreamOwned<rustls::ClientConnection, TcpStream>>),
+}
+
+impl Stream {
+    fn shutdown(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Plain(stream) => stream.shutdown(Shutdown::Both),
+            #[cfg(feature = "tls")]
+            Stream::Tls(stream) => {
+                stream.conn.send_close_notify();
+                let _ = stream.flush();
+                stream.sock.shutdown(Shutdown::Both)
+            }
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::Plain(stream) => stream.read(buf),
+            #[cfg(feature = "tls")]
+            Stream::Tls(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stream::Plain(stream) => stream.write(buf),
+            #[cfg(feature = "tls")]
+            Stream::Tls(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Plain(stream) => stream.flush(),
+            #[cfg(feature = "tls")]
+            Stream::Tls(stream) => stream.flush(),
+        }
+    }
+}
+
+/// Connection to a server, one request at a time
+pub struct Client {
+    addr: SocketAddr,
+    connect_timeout: Duration,
+    /// How long a request may take to send and answer
+    timeout: Duration,
+    #[cfg(feature = "tls")]
+    tls: Option<(
+        Arc<rustls::ClientConfig>,
+        rustls::pki_types::ServerName<'static>,
+    )>,
+    framing: Framing,
+    stream: Option<Stream>,
+    /// With framing, received bytes not yet making up a whole frame
+    received: Vec<u8>,
+    next_request_id: u64,
+    reconnect: Option<ReconnectPolicy>,
+    on_state_change: Option<Box<dyn FnMut(ConnectionState) + Send>>,
+    on_connect: Option<ConnectCallback>,
+}
+
+/// Prepares a new connection, see `Client::on_connect`
+type ConnectCallback = Box<dyn FnMut(&mut Client) -> Result<()> + Send>;
+
+impl Client {
+    /// Creates a client for the server at `addr`; `connect` opens the
+    /// connection
+    pub fn new(addr: SocketAddr) -> Self {
+        Client {
+            addr,
+            connect_timeout: Duration::from_secs(5),
+            timeout: Duration::from_secs(30),
+            #[cfg(feature = "tls")]
+            tls: None,
+            framing: Framing::default(),
+            stream: None,
+            received: Vec::new(),
+            next_request_id: 1,
+            reconnect: None,
+            on_state_change: None,
+            on_connect: None,
+        }
+    }
+
+    /// Sets how long connecting may take
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// Sets how long a request may take to be sent and answered
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets how messages are delimited, which must match the server's
+    pub fn with_framing(mut self, framing: Framing) -> Self {
+        self.framing = framing;
+        self
+    }
+
+    /// Reconnects automatically after the connection is lost
+    pub fn with_reconnect(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect = Some(policy);
+        self
+    }
+
+    /// Calls `callback` whenever the client connects, disconnects or starts
+    /// a reconnect attempt
+    pub fn on_state_change<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(ConnectionState) + Send + 'static,
+    {
+        self.on_state_change = Some(Box::new(callback));
+        self
+    }
+
+    /// Calls `callback` on every new connection before it is used, including
+    /// after reconnecting, e.g. to authenticate. An error from `callback`
+    /// closes the connection and fails the connect.
+    pub fn on_connect<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(&mut Client) -> Result<()> + Send + 'static,
+    {
+        self.on_connect = Some(Box::new(callback));
+        self
+    }
+
+    /// Connects over TLS, verifying the server certificate for `server_name`
+    #[cfg(feature = "tls")]
+    pub fn with_tls(mut self, config: &TlsClientConfig, server_name: &str) -> Result<Self> {
+        let name = rustls::pki_types::ServerName::try_from(server_name.to_string())
+            .map_err(|e| Error::Io(io::Error::new(ErrorKind::InvalidInput, e)))?;
+        self.tls = Some((config.client_config().map_err(Error::Io)?, name));
+        Ok(self)
+    }
+
+    /// Returns the address of the server
+    pub fn address(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    /// Opens the connection, closing the current one if any
+    pub fn connect(&mut self) -> Result<()> {
+        self.stream = None;
+        self.establish()?;
+        self.set_state(ConnectionState::Connected);
+        Ok(())
+    }
+
+    /// Opens the connection and runs the `on_connect` callback on it
+    fn establish(&mut self) -> Result<()> {
+        self.open()?;
+        let Some(mut callback) = self.on_connect.take() else {
+            return Ok(());
+        };
+        // A connection lost during the callback must not be replaced by one
+        // the callback never ran on
+        let policy = self.reconnect.take();
+        let result = callback(self);
+        self.on_connect = Some(callback);
+        self.reconnect = policy;
+        if result.is_err() {
+            if let Some(mut stream) = self.stream.take() {
+                let _ = stream.shutdown();
+            }
+        }
+        result
+    }
+
+    fn open(&mut self) -> Result<()> {
+        let stream =
+            TcpStream::connect_timeout(&self.addr, self.connect_timeout).map_err(Error::Io)?;
+        stream.set_nodelay(true)?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+        self.received.clear();
+
+        #[cfg(feature = "tls")]
+        if let Some((config, name)) = &self.tls {
+            let conn = rustls::ClientConnection::new(config.clone(), name.clone())
+                .map_err(|e| Error::Io(io::Error::other(e)))?;
+            let mut tls = rustls::StreamOwned::new(conn, stream);
+            // Complete the handshake now so certificate errors surface here
+            while tls.conn.is_handshaking() {
+                tls.conn.complete_io(&mut tls.sock).map_err(Error::Io)?;
+            }
+            self.stream = Some(Stream::Tls(Box::new(tls)));
+            debug!("Connected to {} over TLS", self.addr);
+            return Ok(());
+        }
+
+        self.stream = Some(Stream::Plain(stream));
+        debug!("Connected to {}", self.addr);
+        Ok(())
+    }
+
+    /// Closes the connection, if any
+    pub fn disconnect(&mut self) -> Result<()> {
+        if let Some(mut stream) = self.stream.take() {
+            debug!("Disconnected from {}", self.addr);
+            self.set_state(ConnectionState::Disconnected);
+            stream.shutdown()?;
+        }
+        Ok(())
+    }
+
+    /// Connects, retrying with backoff while the request's `retries` are
+    /// within the reconnect policy
+    fn reconnect(&mut self, policy: &ReconnectPolicy, retries: &mut u32) -> Result<()> {
+        let mut attempt = 1;
+        loop {
+            self.set_state(ConnectionState::Reconnecting { attempt });
+            match self.establish() {
+                Ok(()) => {
+                    self.set_state(ConnectionState::Connected);
+                    return Ok(());
+                }
+                // Rejected credentials are not helped by retries
+                Err(e @ (Error::Io(_) | Error::Disconnected | Error::Timeout))
+                    if policy.allows_retry(*retries) =>
+                {
+                    let delay = policy.backoff(*retries);
+                    warn!(
+                        "Failed to reconnect to {}: {}, retrying in {:?}",
+                        self.addr, e, delay
+                    );
+                    thread::sleep(delay);
+                    *retries += 1;
+                    attempt += 1;
+                }
+                Err(e) => {
+                    warn!("Giving up reconnecting to {}: {}", self.addr, e);
+                    self.set_state(ConnectionState::Disconnected);
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    fn set_state(&mut self, state: ConnectionState) {
+        if let Some(callback) = &mut self.on_state_change {
+            callback(state);
+        }
+    }
+
+    /// Sends `content` to be echoed back and returns the echo
+    pub fn echo(&mut self, content: &str) -> Result<String> {
+        let message = client_message::Message::EchoMessage(EchoMessage {
+            content: content.to_string(),
+        });
+        match self.request(message)? {
+            server_message::Message::EchoMessage(echo) => Ok(echo.content),
+            other => Err(Error::UnexpectedResponse(Some(Box::new(other)))),
+        }
+    }
+
+    /// Has the server add `a` and `b`
+    pub fn add(&mut self, a: i32, b: i32) -> Result<i32> {
+        let message = client_message::Message::AddRequest(AddRequest { a, b });
+        match self.request(message)? {
+            server_message::Message::AddResponse(response) => Ok(response.result),
+            other => Err(Error::UnexpectedResponse(Some(Box::new(other)))),
+        }
+    }
+
+    /// Sends any request and waits for its response. `Error` responses are
+    /// returned as `Error::Server`.
+    pub fn request(&mut self, message: client_message::Message) -> Result<server_message::Message> {
+        let request_id = self.next_request_id;
+        self.next_request_id += 1;
+        let retry = is_idempotent(&message);
+        let request = ClientMessage {
+            message: Some(message),
+            request_id,
+        };
+        let policy = self.reconnect.clone();
+
+        // One budget for connecting and resending, so that a request makes
+        // at most `max_retries + 1` connection attempts
+        let mut retries = 0;
+        let response = loop {
+            if let (None, Some(policy)) = (&self.stream, &policy) {
+                self.reconnect(policy, &mut retries)?;
+            }
+            match self.exchange(&request) {
+                // A timed-out response may still arrive and is skipped by its
+                // ID, but after these errors the connection is unusable
+                Err(e @ (Error::Io(_) | Error::Disconnected | Error::Decode(_))) => {
+                    warn!("Dropping connection to {}: {}", self.addr, e);
+                    self.stream = None;
+                    self.set_state(ConnectionState::Disconnected);
+                    match &policy {
+                        // A server that sends garbage is not helped by retries
+                        Some(policy)
+                            if retry
+                                && !matches!(e, Error::Decode(_))
+                                && policy.allows_retry(retries) =>
+                        {
+                            retries += 1
+                        }
+                        _ => return Err(e),
+                    }
+                }
+                result => break result?,
+            }
+        };
+
+        match response.message {
+            Some(server_message::Message::Error(error)) => Err(Error::Server {
+                code: error.code(),
+                message: error.message,
+                field: error.field,
+            }),
+            Some(message) => Ok(message),
+            None => Err(Error::UnexpectedResponse(None)),
+        }
+    }
+
+    fn exchange(&mut self, request: &ClientMessage) -> Result<ServerMessage> {
+        let stream = self.stream.as_mut().ok_or(Error::NotConnected)?;
+        stream.write_all(&self.framing.encode(request))?;
+        stream.flush()?;
+
+        let mut buffer = vec![0; MAX_MESSAGE_SIZE];
+        loop {
+            let response = match self.framing {
+                Framing::Unframed => {
+                    let bytes_read = stream.read(&mut buffer)?;
+                    if bytes_read == 0 {
+                        return Err(Error::Disconnected);
+                    }
+                    ServerMessage::decode(&buffer[..bytes_read])?
+                }
+                Framing::LengthDelimited => match framing::next_frame(&self.received)? {
+                    Some(frame) => {
+                        let response = ServerMessage::decode(&self.received[frame.clone()]);
+                        self.received.drain(..frame.end);
+                        response?
+                    }
+                    None => {
+                        let bytes_read = stream.read(&mut buffer)?;
+                        if bytes_read == 0 {
+                            return Err(Error::Disconnected);
+                        }
+                        self.received.extend_from_slice(&buffer[..bytes_read]);
+                        continue;
+                    }
+                },
+            };
+            if response.request_id == request.request_id {
+                return Ok(response);
+            }
+            debug!(
+                "Skipping response to request {} while waiting for {}",
+                response.request_id, request.request_id
+            );
+        }
+    }
+}
@@ -0,0 +1,187 @@
+//! Registry of the connections a server is serving, with per-connection
+//! counters and the means to disconnect them.
+
+use crate::listener::{ConnectionInfo, StreamCloser};
+use std::{
+    collections::BTreeMap,
+    io,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant, SystemTime},
+};
+
+/// Identifies a connection for as long as the server runs
+pub type ConnectionId = u64;
+
+/// Traffic counters of one connection
+#[derive(Debug, Default)]
+pub struct ConnectionStats {
+    requests: AtomicU64,
+    decode_errors: AtomicU64,
+    bytes_received: AtomicU64,
+    bytes_sent: AtomicU64,
+}
+
+impl ConnectionStats {
+    pub fn request(&self) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn decode_error(&self) {
+        self.decode_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn bytes_received(&self, count: usize) {
+        self.bytes_received
+            .fetch_add(count as u64, Ordering::Relaxed);
+    }
+
+    pub fn bytes_sent(&self, count: usize) {
+        self.bytes_sent.fetch_add(count as u64, Ordering::Relaxed);
+    }
+}
+
+/// A connection as tracked by the registry
+pub struct Connection {
+    id: ConnectionId,
+    info: ConnectionInfo,
+    connected_at: SystemTime,
+    started: Instant,
+    stats: ConnectionStats,
+    closer: Option<StreamCloser>,
+    /// Who the connection authenticated as, if it did
+    identity: Mutex<Option<String>>,
+}
+
+impl Connection {
+    pub fn id(&self) -> ConnectionId {
+        self.id
+    }
+
+    pub fn stats(&self) -> &ConnectionStats {
+        &self.stats
+    }
+
+    pub fn identity(&self) -> Option<String> {
+        self.identity.lock().unwrap().clone()
+    }
+
+    pub fn set_identity(&self, identity: Option<String>) {
+        *self.identity.lock().unwrap() = identity;
+    }
+
+    pub fn snapshot(&self) -> ConnectionSnapshot {
+        ConnectionSnapshot {
+            id: self.id,
+            info: self.info.clone(),
+            identity: self.identity(),
+            connected_at: self.connected_at,
+            connected_for: self.started.elapsed(),
+            requests: self.stats.requests.load(Ordering::Relaxed),
+            decode_errors: self.stats.decode_errors.load(Ordering::Relaxed),
+            bytes_received: self.stats.bytes_received.load(Ordering::Relaxed),
+            bytes_sent: self.stats.bytes_sent.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Point-in-time description of a connection
+#[derive(Debug, Clone)]
+pub struct ConnectionSnapshot {
+    pub id: ConnectionId,
+    pub info: ConnectionInfo,
+    pub identity: Option<String>,
+    pub connected_at: SystemTime,
+    pub connected_for: Duration,
+    pub requests: u64,
+    pub decode_errors: u64,
+    pub bytes_received: u64,
+    pub bytes_sent: u64,
+}
+
+/// Connections currently open, by ID
+#[derive(Default)]
+pub struct ConnectionRegistry {
+    connections: Mutex<BTreeMap<ConnectionId, Arc<Connection>>>,
+}
+
+impl ConnectionRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a connection. `closer` lets `disconnect` end it; connections
+    /// registered without one cannot be disconnected.
+    pub fn register(
+        &self,
+        id: ConnectionId,
+        info: ConnectionInfo,
+        closer: Option<StreamCloser>,
+    ) -> Arc<Connection> {
+        let connection = Arc::new(Connection {
+            id,
+            info,
+            connected_at: SystemTime::now(),
+            started: Instant::now(),
+            stats: ConnectionStats::default(),
+            closer,
+            identity: Mutex::new(None),
+        });
+        self.connections
+            .lock()
+            .unwrap()
+            .insert(id, connection.clone());
+        connection
+    }
+
+    pub fn remove(&self, id: ConnectionId) {
+        self.connections.lock().unwrap().remove(&id);
+    }
+
+    pub fn len(&self) -> usize {
+        self.connections.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns every open connection, ordered by ID
+    pub fn list(&self) -> Vec<ConnectionSnapshot> {
+        self.connections
+            .lock()
+            .unwrap()
+            .values()
+            .map(|connection| connection.snapshot())
+            .collect()
+    }
+
+    pub fn get(&self, id: ConnectionId) -> Option<ConnectionSnapshot> {
+        self.connections
+            .lock()
+            .unwrap()
+            .get(&id)
+            .map(|connection| connection.snapshot())
+    }
+
+    /// Closes the connection's socket. Its thread then sees the connection
+    /// end and removes it from the registry.
+    pub fn disconnect(&self, id: ConnectionId) -> io::Result<()> {
+        let connection = self.connections.lock().unwrap().get(&id).cloned();
+        let Some(connection) = connection else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no connection {}", id),
+            ));
+        };
+        match &connection.closer {
+            Some(closer) => closer.close(),
+            None => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("connection {} cannot be closed", id),
+            )),
+        }
+    }
+}
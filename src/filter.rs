@@ -0,0 +1,118 @@
+//! IP allow and deny lists applied to accepted connections.
+//!
+//! The server checks every connection's peer address against its `IpFilter`
+//! (see `Server::with_ip_filter`) right after `accept()`, before a thread is
+//! spawned for it. A filter can be replaced while the server runs, or
+//! reloaded from its file with `Server::reload_ip_filter` or the admin
+//! channel's `reload-filter`.
+
+use ipnet::IpNet;
+use std::{
+    fmt, fs,
+    io::{self, ErrorKind},
+    net::IpAddr,
+    path::{Path, PathBuf},
+};
+
+/// CIDR allow and deny lists.
+///
+/// An address is refused if it is in a denied network, or if there are
+/// allowed networks and it is in none of them. An empty filter accepts every
+/// address.
+#[derive(Debug, Clone, Default)]
+pub struct IpFilter {
+    allow: Vec<IpNet>,
+    deny: Vec<IpNet>,
+    /// File the filter was loaded from, to reload it
+    source: Option<PathBuf>,
+}
+
+impl IpFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads a filter file with one rule per line:
+    ///
+    /// ```text
+    /// # comment
+    /// allow 10.20.0.0/16
+    /// deny 10.20.99.0/24
+    /// allow fd00::/8
+    /// ```
+    ///
+    /// A bare address stands for a single host.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut filter = Self::new();
+        for (number, line) in fs::read_to_string(&path)?.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let result = match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+                ["allow", network] => filter.allow(network),
+                ["deny", network] => filter.deny(network),
+                _ => Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    "expected `allow <network>` or `deny <network>`",
+                )),
+            };
+            filter = result
+                .map_err(|e| io::Error::new(e.kind(), format!("line {}: {}", number + 1, e)))?;
+        }
+        filter.source = Some(path.as_ref().to_path_buf());
+        Ok(filter)
+    }
+
+    /// Adds a network addresses must be in, e.g. `10.20.0.0/16`
+    pub fn allow(mut self, network: &str) -> io::Result<Self> {
+        self.allow.push(parse_network(network)?);
+        Ok(self)
+    }
+
+    /// Adds a network whose addresses are refused
+    pub fn deny(mut self, network: &str) -> io::Result<Self> {
+        self.deny.push(parse_network(network)?);
+        Ok(self)
+    }
+
+    /// Returns the file the filter was loaded from, if any
+    pub fn source(&self) -> Option<&Path> {
+        self.source.as_deref()
+    }
+
+    /// Whether connections from `addr` are accepted
+    pub fn allows(&self, addr: IpAddr) -> bool {
+        // IPv4 clients of dual-stack sockets show up as IPv4-mapped addresses
+        let addr = addr.to_canonical();
+        if self.deny.iter().any(|network| network.contains(&addr)) {
+            return false;
+        }
+        self.allow.is_empty() || self.allow.iter().any(|network| network.contains(&addr))
+    }
+}
+
+impl fmt::Display for IpFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for network in &self.allow {
+            writeln!(f, "allow {}", network)?;
+        }
+        for network in &self.deny {
+            writeln!(f, "deny {}", network)?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_network(network: &str) -> io::Result<IpNet> {
+    network
+        .parse::<IpNet>()
+        .or_else(|_| network.parse::<IpAddr>().map(IpNet::from))
+        .map(|network| network.trunc())
+        .map_err(|_| {
+            io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("invalid network {:?}", network),
+            )
+        })
+}
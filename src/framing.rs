@@ -0,0 +1,81 @@
+//! Message framing on stream connections.
+//!
+//! By default every read on a connection is taken to be one whole message,
+//! which holds while a peer sends its next request only after the response
+//! to the previous one. Peers that have several requests in flight, like
+//! `AsyncClient`, need `Framing::LengthDelimited`: each message is preceded
+//! by its length as a protobuf varint, as written by prost's
+//! `Message::encode_length_delimited`. Both ends of a connection must use
+//! the same framing; `Client::with_framing` and the replay tool's
+//! `--length-delimited` talk to a server set up with `Server::with_framing`.
+
+use prost::Message;
+use std::{
+    io::{self, ErrorKind},
+    ops::Range,
+};
+
+/// How messages are delimited on a connection
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Framing {
+    /// One message per read
+    #[default]
+    Unframed,
+    /// Each message is preceded by its length as a varint
+    LengthDelimited,
+}
+
+impl Framing {
+    /// Encodes `message` for the wire
+    pub fn encode(self, message: &impl Message) -> Vec<u8> {
+        match self {
+            Framing::Unframed => message.encode_to_vec(),
+            Framing::LengthDelimited => message.encode_length_delimited_to_vec(),
+        }
+    }
+
+    /// Delimits an already encoded message for the wire
+    pub fn frame(self, message: &[u8]) -> Vec<u8> {
+        match self {
+            Framing::Unframed => message.to_vec(),
+            Framing::LengthDelimited => {
+                let mut frame = Vec::with_capacity(MAX_PREFIX_SIZE + message.len());
+                prost::encoding::encode_varint(message.len() as u64, &mut frame);
+                frame.extend_from_slice(message);
+                frame
+            }
+        }
+    }
+}
+
+/// Largest framed message accepted, matching the read buffers
+pub const MAX_FRAME_SIZE: usize = 65536;
+
+/// Longest varint encoding of a length
+const MAX_PREFIX_SIZE: usize = 10;
+
+/// Returns where the message of the first frame in `buffer` is, or `None`
+/// while the frame is incomplete. Fails if the length prefix is invalid or
+/// exceeds `MAX_FRAME_SIZE`.
+pub fn next_frame(buffer: &[u8]) -> io::Result<Option<Range<usize>>> {
+    let mut rest = buffer;
+    let length = match prost::decode_length_delimiter(&mut rest) {
+        Ok(length) => length,
+        // Every byte of an unfinished varint has the continuation bit set
+        Err(_) if buffer.len() < MAX_PREFIX_SIZE && buffer.iter().all(|b| b & 0x80 != 0) => {
+            return Ok(None)
+        }
+        Err(e) => return Err(io::Error::new(ErrorKind::InvalidData, e)),
+    };
+    if length > MAX_FRAME_SIZE {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!("frame of {} bytes exceeds {} bytes", length, MAX_FRAME_SIZE),
+        ));
+    }
+    let start = buffer.len() - rest.len();
+    if rest.len() < length {
+        return Ok(None);
+    }
+    Ok(Some(start..start + length))
+}
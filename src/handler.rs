@@ -0,0 +1,229 @@
+use crate::connection::Connection;
+use crate::health::Health;
+use crate::message::{
+    client_message, server_message, AddResponse, ClientMessage, ConnectionCounters, Error,
+    ErrorCode, ServerMessage, StatsResponse,
+};
+use crate::metrics::Metrics;
+use crate::validation::{self, ValidationRules};
+use tracing::{info, warn};
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ClientMessageWrapper {
+    #[prost(oneof = "client_message::Message", tags = "1, 2, 3, 4, 5, 6")]
+    pub message: Option<client_message::Message>,
+    #[prost(uint64, tag = "15")]
+    pub request_id: u64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ServerMessageWrapper {
+    #[prost(oneof = "server_message::Message", tags = "1, 2, 3, 4, 5, 6, 7")]
+    pub message: Option<server_message::Message>,
+    #[prost(uint64, tag = "15")]
+    pub request_id: u64,
+}
+
+impl From<ClientMessage> for ClientMessageWrapper {
+    fn from(message: ClientMessage) -> Self {
+        ClientMessageWrapper {
+            message: message.message,
+            request_id: message.request_id,
+        }
+    }
+}
+
+impl From<ServerMessageWrapper> for ServerMessage {
+    fn from(wrapper: ServerMessageWrapper) -> Self {
+        ServerMessage {
+            message: wrapper.message,
+            request_id: wrapper.request_id,
+        }
+    }
+}
+
+/// Names of the `ClientMessage` variants, as returned by `request_type`, in
+/// field order. `policy_test` checks that every variant is listed.
+pub const REQUEST_TYPES: &[&str] = &[
+    "echo_message",
+    "add_request",
+    "health_check_request",
+    "stats_request",
+    "auth_challenge_request",
+    "auth_request",
+];
+
+/// Returns the name of the request's message type, as used in metrics
+pub fn request_type(request: &ClientMessageWrapper) -> &'static str {
+    match request.message {
+        Some(client_message::Message::EchoMessage(_)) => "echo_message",
+        Some(client_message::Message::AddRequest(_)) => "add_request",
+        Some(client_message::Message::HealthCheckRequest(_)) => "health_check_request",
+        Some(client_message::Message::StatsRequest(_)) => "stats_request",
+        Some(client_message::Message::AuthChallengeRequest(_)) => "auth_challenge_request",
+        Some(client_message::Message::AuthRequest(_)) => "auth_request",
+        None => "none",
+    }
+}
+
+/// Builds the `Error` sent in place of the response to a rejected request
+pub fn error_response(
+    request_id: u64,
+    code: ErrorCode,
+    message: impl Into<String>,
+) -> ServerMessageWrapper {
+    ServerMessageWrapper {
+        message: Some(server_message::Message::Error(Error {
+            code: code as i32,
+            message: message.into(),
+            field: String::new(),
+        })),
+        request_id,
+    }
+}
+
+/// Builds the `InvalidArgument` error for a request whose `field` is wrong
+pub fn invalid_argument(
+    request_id: u64,
+    field: impl Into<String>,
+    message: impl Into<String>,
+) -> ServerMessageWrapper {
+    ServerMessageWrapper {
+        message: Some(server_message::Message::Error(Error {
+            code: ErrorCode::InvalidArgument as i32,
+            message: message.into(),
+            field: field.into(),
+        })),
+        request_id,
+    }
+}
+
+/// What the handlers can report about whatever serves the request
+pub struct RequestContext<'a> {
+    pub health: &'a Health,
+    /// Server-wide counters, on transports that keep them
+    pub metrics: Option<&'a Metrics>,
+    /// The connection the request arrived on, on connection-oriented transports
+    pub connection: Option<&'a Connection>,
+    /// Rules requests are checked against before they are handled
+    pub validation: Option<&'a ValidationRules>,
+}
+
+impl<'a> RequestContext<'a> {
+    /// A context with only the lifecycle state
+    pub fn new(health: &'a Health) -> Self {
+        RequestContext {
+            health,
+            metrics: None,
+            connection: None,
+            validation: None,
+        }
+    }
+
+    /// Statistics known in this context; unknown counters are left at zero
+    pub fn stats(&self) -> StatsResponse {
+        let mut stats = StatsResponse::default();
+        if let Some(metrics) = self.metrics {
+            let snapshot = metrics.snapshot();
+            stats.uptime_ms = snapshot.uptime.as_millis() as u64;
+            stats.active_connections = snapshot.connections_active;
+            stats.connections_accepted = snapshot.connections_accepted;
+            stats.requests = snapshot.requests.into_iter().collect();
+            stats.decode_errors = snapshot.decode_errors;
+            stats.bytes_received = snapshot.bytes_received;
+            stats.bytes_sent = snapshot.bytes_sent;
+        }
+        stats.connection = self.connection.map(|connection| {
+            let snapshot = connection.snapshot();
+            ConnectionCounters {
+                id: snapshot.id,
+                connected_for_ms: snapshot.connected_for.as_millis() as u64,
+                requests: snapshot.requests,
+                decode_errors: snapshot.decode_errors,
+                bytes_received: snapshot.bytes_received,
+                bytes_sent: snapshot.bytes_sent,
+            }
+        });
+        stats
+    }
+}
+
+/// Processes a decoded request and builds the response to send back.
+///
+/// This is shared by every transport so that they all behave the same way.
+/// Returns `None` when the request carries nothing to respond to.
+pub fn handle_request(
+    request: ClientMessageWrapper,
+    context: &RequestContext,
+) -> Option<ServerMessageWrapper> {
+    if let Some(Err(violation)) = context.validation.map(|rules| rules.validate(&request)) {
+        warn!("Rejected invalid request: {}", violation);
+        return Some(invalid_argument(
+            request.request_id,
+            violation.field,
+            violation.to_string(),
+        ));
+    }
+
+    let message = match request.message {
+        Some(client_message::Message::EchoMessage(echo_message)) => {
+            info!("Received EchoMessage: {}", echo_message.content);
+            server_message::Message::EchoMessage(echo_message)
+        }
+        Some(client_message::Message::AddRequest(add_request)) => {
+            info!(
+                "Received AddRequest: a = {}, b = {}",
+                add_request.a, add_request.b
+            );
+
+            // Validation refuses overflowing sums, but not every transport
+            // validates and one request must not panic the one serving it
+            let result = match validation::checked_sum(add_request.a, add_request.b) {
+                Ok(result) => result,
+                Err(violation) => {
+                    warn!("AddRequest overflows i32");
+                    return Some(invalid_argument(
+                        request.request_id,
+                        violation.field,
+                        violation.to_string(),
+                    ));
+                }
+            };
+            info!("Sending AddResponse: result = {}", result);
+            server_message::Message::AddResponse(AddResponse { result })
+        }
+        Some(client_message::Message::HealthCheckRequest(_)) => {
+            let response = context.health.response();
+            info!(
+                "Sending HealthCheckResponse: status = {:?}",
+                response.status()
+            );
+            server_message::Message::HealthCheckResponse(response)
+        }
+        Some(client_message::Message::StatsRequest(_)) => {
+            info!("Received StatsRequest");
+            server_message::Message::StatsResponse(context.stats())
+        }
+        // Servers with credentials answer these before the handlers run
+        Some(
+            client_message::Message::AuthChallengeRequest(_)
+            | client_message::Message::AuthRequest(_),
+        ) => {
+            warn!("Received an authentication request, but authentication is disabled.");
+            return Some(error_response(
+                request.request_id,
+                ErrorCode::Unsupported,
+                "authentication is not enabled",
+            ));
+        }
+        None => {
+            warn!("Received message with None type.");
+            return None;
+        }
+    };
+
+    Some(ServerMessageWrapper {
+        message: Some(message),
+        request_id: request.request_id,
+    })
+}
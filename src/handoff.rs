@@ -0,0 +1,236 @@
+//! Zero-downtime restarts by handing the listening sockets to a new process.
+//!
+//! A running server offers its listeners on a Unix socket. The new process
+//! connects and receives them as file descriptors (SCM_RIGHTS), then starts
+//! accepting while the old process stops accepting and drains the connections
+//! it already has. The listening sockets stay open throughout, so no
+//! connection attempt is refused during an upgrade.
+//!
+//! The exchange on the handoff socket is:
+//! 1. old to new: one kind byte per listener, with the descriptors attached
+//! 2. new to old: `ACK` once every listener has been adopted
+//! 3. the old process removes its handoff socket and closes the connection,
+//!    after which the new process may bind the same path for the next upgrade
+
+use crate::listener::Listener;
+use std::{
+    io::{self, Read, Write},
+    mem,
+    net::TcpListener,
+    os::unix::{
+        io::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+        net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+    ptr,
+    time::Duration,
+};
+use tracing::info;
+
+/// Most listeners a server can hand over in one go
+const MAX_LISTENERS: usize = 32;
+
+/// Sent by the new process once it owns the listeners
+const ACK: u8 = b'A';
+
+/// How long either side waits for the other during the exchange
+const HANDOFF_TIMEOUT: Duration = Duration::from_secs(5);
+
+const KIND_TCP: u8 = b't';
+#[cfg(feature = "websocket")]
+const KIND_WEBSOCKET: u8 = b'w';
+const KIND_UNIX: u8 = b'u';
+
+/// Checks that every listener can be handed over.
+///
+/// TLS listeners cannot: their configuration lives in the old process.
+pub fn check_listeners(listeners: &[Listener]) -> io::Result<()> {
+    if listeners.len() > MAX_LISTENERS {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("at most {} listeners can be handed over", MAX_LISTENERS),
+        ));
+    }
+    listeners
+        .iter()
+        .try_for_each(|listener| kind(listener).map(drop))
+}
+
+/// Sends the listeners over `stream` and waits for the new process to
+/// acknowledge them. On success the caller must stop accepting.
+pub fn send_listeners(stream: &mut UnixStream, listeners: &[Listener]) -> io::Result<()> {
+    check_listeners(listeners)?;
+    let kinds = listeners.iter().map(kind).collect::<io::Result<Vec<_>>>()?;
+    let fds: Vec<RawFd> = listeners.iter().map(AsRawFd::as_raw_fd).collect();
+    send_fds(stream, &kinds, &fds)?;
+
+    stream.set_read_timeout(Some(HANDOFF_TIMEOUT))?;
+    let mut ack = [0u8; 1];
+    stream.read_exact(&mut ack)?;
+    if ack[0] != ACK {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "unexpected handoff acknowledgement",
+        ));
+    }
+    Ok(())
+}
+
+/// Receives the listeners of the server offering them on `path`.
+///
+/// Returns once the old server has stopped accepting and released the
+/// handoff socket.
+pub fn receive_listeners<P: AsRef<Path>>(path: P) -> io::Result<Vec<Listener>> {
+    let mut stream = UnixStream::connect(path)?;
+    stream.set_read_timeout(Some(HANDOFF_TIMEOUT))?;
+
+    let (kinds, fds) = recv_fds(&stream)?;
+    if kinds.is_empty() || kinds.len() != fds.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "received {} listener kinds for {} descriptors",
+                kinds.len(),
+                fds.len()
+            ),
+        ));
+    }
+    let listeners = kinds
+        .into_iter()
+        .zip(fds)
+        .map(|(kind, fd)| adopt(kind, fd))
+        .collect::<io::Result<Vec<_>>>()?;
+
+    stream.write_all(&[ACK])?;
+    // Wait for the old server to let go of the handoff socket
+    stream.read_to_end(&mut Vec::new())?;
+    info!("Took over {} listener(s)", listeners.len());
+    Ok(listeners)
+}
+
+fn kind(listener: &Listener) -> io::Result<u8> {
+    match listener {
+        Listener::Tcp(_) => Ok(KIND_TCP),
+        #[cfg(feature = "tls")]
+        Listener::Tls(..) => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "TLS listeners cannot be handed over",
+        )),
+        #[cfg(feature = "websocket")]
+        Listener::WebSocket(_) => Ok(KIND_WEBSOCKET),
+        Listener::Unix(..) => Ok(KIND_UNIX),
+    }
+}
+
+fn adopt(kind: u8, fd: OwnedFd) -> io::Result<Listener> {
+    match kind {
+        KIND_TCP => Ok(Listener::Tcp(TcpListener::from(fd))),
+        #[cfg(feature = "websocket")]
+        KIND_WEBSOCKET => Ok(Listener::WebSocket(TcpListener::from(fd))),
+        KIND_UNIX => {
+            // The socket file is ours to remove from now on
+            let listener = UnixListener::from(fd);
+            let path = listener.local_addr()?.as_pathname().map(PathBuf::from);
+            Ok(Listener::Unix(listener, path))
+        }
+        _ => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("cannot adopt listener of kind {:?}", kind as char),
+        )),
+    }
+}
+
+/// Buffer for a control message carrying `count` descriptors, made of `u64`s
+/// so that it is aligned for `cmsghdr`, along with its length in bytes
+fn control_buffer(count: usize) -> (Vec<u64>, usize) {
+    // SAFETY: CMSG_SPACE only computes a size
+    let space = unsafe { libc::CMSG_SPACE((count * mem::size_of::<RawFd>()) as u32) } as usize;
+    (vec![0; space.div_ceil(mem::size_of::<u64>())], space)
+}
+
+fn send_fds(stream: &UnixStream, data: &[u8], fds: &[RawFd]) -> io::Result<()> {
+    let (mut control, control_len) = control_buffer(fds.len());
+    let mut iov = libc::iovec {
+        iov_base: data.as_ptr() as *mut libc::c_void,
+        iov_len: data.len(),
+    };
+    // SAFETY: an all-zero msghdr is a valid empty message
+    let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr().cast();
+    msg.msg_controllen = control_len as _;
+
+    // SAFETY: the control buffer has room for one header and `fds`, and
+    // `msg` only points at buffers that outlive the sendmsg call
+    let sent = unsafe {
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        (*cmsg).cmsg_level = libc::SOL_SOCKET;
+        (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(mem::size_of_val(fds) as u32) as _;
+        ptr::copy_nonoverlapping(fds.as_ptr(), libc::CMSG_DATA(cmsg).cast(), fds.len());
+        libc::sendmsg(stream.as_raw_fd(), &msg, 0)
+    };
+    match sent {
+        -1 => Err(io::Error::last_os_error()),
+        sent if sent as usize != data.len() => Err(io::Error::new(
+            io::ErrorKind::WriteZero,
+            "handoff message was truncated",
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn recv_fds(stream: &UnixStream) -> io::Result<(Vec<u8>, Vec<OwnedFd>)> {
+    let mut data = vec![0u8; MAX_LISTENERS];
+    let (mut control, control_len) = control_buffer(MAX_LISTENERS);
+    let mut iov = libc::iovec {
+        iov_base: data.as_mut_ptr().cast(),
+        iov_len: data.len(),
+    };
+    // SAFETY: an all-zero msghdr is a valid empty message
+    let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr().cast();
+    msg.msg_controllen = control_len as _;
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    let flags = libc::MSG_CMSG_CLOEXEC;
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    let flags = 0;
+    // SAFETY: `msg` points at buffers that outlive the recvmsg call
+    let received = unsafe { libc::recvmsg(stream.as_raw_fd(), &mut msg, flags) };
+    if received == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let mut fds = Vec::new();
+    // SAFETY: the kernel filled in the control messages walked here, and
+    // every descriptor in an SCM_RIGHTS message is newly owned by us
+    unsafe {
+        let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_RIGHTS {
+                let len = (*cmsg).cmsg_len as usize - libc::CMSG_LEN(0) as usize;
+                let first = libc::CMSG_DATA(cmsg).cast::<RawFd>();
+                for i in 0..len / mem::size_of::<RawFd>() {
+                    let fd = first.add(i).read_unaligned();
+                    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+                    libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC);
+                    fds.push(OwnedFd::from_raw_fd(fd));
+                }
+            }
+            cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+        }
+    }
+    if msg.msg_flags & libc::MSG_CTRUNC != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "handoff descriptors were truncated",
+        ));
+    }
+
+    data.truncate(received as usize);
+    Ok((data, fds))
+}
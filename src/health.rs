@@ -0,0 +1,116 @@
+//! Liveness and readiness reporting.
+//!
+//! A server's lifecycle state is answered to `HealthCheckRequest` messages on
+//! every transport and, with the `http` feature, on `/healthz` and `/readyz`
+//! through a `HealthEndpoint`.
+
+#[cfg(feature = "http")]
+use crate::http_server::HttpServer;
+use crate::message::{HealthCheckResponse, ServingStatus};
+use std::sync::atomic::{AtomicI32, Ordering};
+#[cfg(feature = "http")]
+use std::{io, sync::Arc};
+#[cfg(feature = "http")]
+use tracing::error;
+
+/// Lifecycle state of a server, shared with whatever reports it
+#[derive(Debug)]
+pub struct Health {
+    status: AtomicI32,
+}
+
+impl Health {
+    pub fn new(status: ServingStatus) -> Self {
+        Health {
+            status: AtomicI32::new(status as i32),
+        }
+    }
+
+    pub fn status(&self) -> ServingStatus {
+        ServingStatus::try_from(self.status.load(Ordering::SeqCst)).unwrap_or_default()
+    }
+
+    pub fn set(&self, status: ServingStatus) {
+        self.status.store(status as i32, Ordering::SeqCst);
+    }
+
+    /// Whether new connections are accepted
+    pub fn is_ready(&self) -> bool {
+        self.status() == ServingStatus::Serving
+    }
+
+    /// Whether the server is still doing its job, including while starting
+    /// and draining
+    pub fn is_live(&self) -> bool {
+        self.status() != ServingStatus::Stopped
+    }
+
+    pub fn response(&self) -> HealthCheckResponse {
+        let status = self.status();
+        HealthCheckResponse {
+            status: status as i32,
+            ready: status == ServingStatus::Serving,
+        }
+    }
+}
+
+/// HTTP endpoint for orchestrator probes.
+///
+/// `GET /healthz` answers 200 while the server is live and `GET /readyz` 200
+/// while it is ready; both answer 503 otherwise, with the status in the body.
+#[cfg(feature = "http")]
+pub struct HealthEndpoint {
+    server: HttpServer,
+    health: Arc<Health>,
+}
+
+#[cfg(feature = "http")]
+impl HealthEndpoint {
+    /// Creates a new endpoint bound to `addr`, reporting `health`
+    pub fn new(addr: &str, health: Arc<Health>) -> io::Result<Self> {
+        Ok(HealthEndpoint {
+            server: HttpServer::bind(addr, "Health endpoint")?,
+            health,
+        })
+    }
+
+    /// Returns the endpoint's address
+    pub fn address(&self) -> &str {
+        self.server.address()
+    }
+
+    /// Runs the endpoint, answering probes until `stop()` is called
+    pub fn run(&self) -> io::Result<()> {
+        self.server.run(|request| self.handle(request))
+    }
+
+    /// Stops the endpoint
+    pub fn stop(&self) {
+        self.server.stop();
+    }
+
+    fn handle(&self, request: tiny_http::Request) {
+        use tiny_http::{Method, Response};
+
+        let path = request.url().split('?').next().unwrap_or_default();
+        let healthy = match path {
+            "/healthz" => Some(self.health.is_live()),
+            "/readyz" => Some(self.health.is_ready()),
+            _ => None,
+        };
+        let response = match healthy {
+            None => Response::from_string(format!("no route for {}\n", path)).with_status_code(404),
+            Some(_) if *request.method() != Method::Get => {
+                Response::from_string(format!("{} requires GET\n", path)).with_status_code(405)
+            }
+            Some(healthy) => {
+                let status = self.health.status().as_str_name().to_lowercase();
+                let code = if healthy { 200 } else { 503 };
+                Response::from_string(format!("{}\n", status)).with_status_code(code)
+            }
+        };
+        if let Err(e) = request.respond(response) {
+            error!("Failed to send health response: {}", e);
+        }
+    }
+}
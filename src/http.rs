@@ -0,0 +1,148 @@
+use crate::handler::{self, ClientMessageWrapper, RequestContext};
+use crate::health::Health;
+use crate::http_server::HttpServer;
+use crate::message::{client_message, ClientMessage, ServerMessage, ServingStatus};
+use crate::validation::ValidationRules;
+use serde_json::{json, Value};
+use std::io::{self, Read};
+use tiny_http::{Header, Method, Request, Response};
+use tracing::{error, info, warn};
+
+/// Largest request body accepted, matching the TCP read buffer
+const MAX_BODY_SIZE: u64 = 65536;
+
+/// Maps gateway paths onto `ClientMessage` variants, by their JSON names.
+/// The body of a request to one of these paths is the variant's message.
+const ROUTES: &[(&str, &str)] = &[("/echo", "echo_message"), ("/add", "add_request")];
+
+/// Path accepting a complete `ClientMessage` in JSON, for any request type
+const MESSAGE_ROUTE: &str = "/message";
+
+/// HTTP listener mapping JSON requests onto the protobuf messages, for
+/// debugging with curl and for scripts
+pub struct HttpGateway {
+    server: HttpServer,
+    health: Health,
+    /// Rules requests must satisfy before they are handled
+    validation: ValidationRules,
+}
+
+impl HttpGateway {
+    /// Creates a new gateway bound to `addr`
+    pub fn new(addr: &str) -> io::Result<Self> {
+        Ok(HttpGateway {
+            server: HttpServer::bind(addr, "HTTP gateway")?,
+            health: Health::new(ServingStatus::Starting),
+            validation: ValidationRules::default(),
+        })
+    }
+
+    /// Checks requests against `rules` before they are handled, like
+    /// `Server::with_validation`
+    pub fn with_validation(mut self, rules: ValidationRules) -> Self {
+        self.validation = rules;
+        self
+    }
+
+    /// Returns the gateway's address
+    pub fn address(&self) -> &str {
+        self.server.address()
+    }
+
+    /// Runs the gateway, answering requests until `stop()` is called
+    pub fn run(&self) -> io::Result<()> {
+        self.health.set(ServingStatus::Serving);
+        let result = self.server.run(|request| self.handle(request));
+        self.health.set(ServingStatus::Stopped);
+        result
+    }
+
+    /// Stops the gateway
+    pub fn stop(&self) {
+        self.server.stop();
+    }
+
+    fn handle(&self, mut request: Request) {
+        info!(
+            "{} {} from {:?}",
+            request.method(),
+            request.url(),
+            request.remote_addr()
+        );
+
+        let (status, body) = match self.process(&mut request) {
+            Ok(response) => (200, response),
+            Err((status, message)) => {
+                warn!("Rejected HTTP request: {}", message);
+                (status, json!({ "error": message }))
+            }
+        };
+
+        let content_type =
+            Header::from_bytes("Content-Type", "application/json").expect("static header is valid");
+        let response = Response::from_string(body.to_string())
+            .with_status_code(status)
+            .with_header(content_type);
+        if let Err(e) = request.respond(response) {
+            error!("Failed to send HTTP response: {}", e);
+        }
+    }
+
+    fn process(&self, request: &mut Request) -> Result<Value, (u16, String)> {
+        let path = request
+            .url()
+            .split('?')
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        let route = ROUTES.iter().find(|(route, _)| *route == path);
+        if route.is_none() && path != MESSAGE_ROUTE {
+            return Err((404, format!("no route for {}", path)));
+        }
+        if *request.method() != Method::Post {
+            return Err((405, format!("{} requires POST", path)));
+        }
+
+        let body = read_body(request)?;
+        let message = match route {
+            Some((_, variant)) => {
+                let message: client_message::Message =
+                    serde_json::from_value(json!({ *variant: body }))
+                        .map_err(|e| (400, format!("invalid {} body: {}", variant, e)))?;
+                ClientMessage {
+                    message: Some(message),
+                    ..Default::default()
+                }
+            }
+            None => serde_json::from_value(body)
+                .map_err(|e| (400, format!("invalid ClientMessage: {}", e)))?,
+        };
+
+        let response = handler::handle_request(
+            ClientMessageWrapper::from(message),
+            &RequestContext {
+                validation: Some(&self.validation),
+                ..RequestContext::new(&self.health)
+            },
+        )
+        .ok_or_else(|| (400, "request carries no message".to_string()))?;
+        serde_json::to_value(ServerMessage::from(response))
+            .map_err(|e| (500, format!("failed to encode response: {}", e)))
+    }
+}
+
+fn read_body(request: &mut Request) -> Result<Value, (u16, String)> {
+    let mut body = Vec::new();
+    request
+        .as_reader()
+        .take(MAX_BODY_SIZE + 1)
+        .read_to_end(&mut body)
+        .map_err(|e| (400, format!("failed to read body: {}", e)))?;
+    if body.len() as u64 > MAX_BODY_SIZE {
+        return Err((413, format!("body exceeds {} bytes", MAX_BODY_SIZE)));
+    }
+    if body.is_empty() {
+        return Ok(json!({}));
+    }
+    serde_json::from_slice(&body).map_err(|e| (400, format!("invalid JSON: {}", e)))
+}
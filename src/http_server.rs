@@ -0,0 +1,61 @@
+//! Accept loop shared by the HTTP listeners.
+
+use std::{
+    io,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+use tracing::{error, info, warn};
+
+/// tiny_http listener answering requests until stopped
+pub(crate) struct HttpServer {
+    server: tiny_http::Server,
+    is_running: AtomicBool,
+    address: String,
+    /// What the listener is, for logging, e.g. "Health endpoint"
+    name: &'static str,
+}
+
+impl HttpServer {
+    /// Binds a listener to `addr`
+    pub(crate) fn bind(addr: &str, name: &'static str) -> io::Result<Self> {
+        let server = tiny_http::Server::http(addr).map_err(io::Error::other)?;
+        let address = server.server_addr().to_string();
+        Ok(HttpServer {
+            server,
+            is_running: AtomicBool::new(false),
+            address,
+            name,
+        })
+    }
+
+    pub(crate) fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Passes requests to `handle` until `stop()` is called
+    pub(crate) fn run(&self, handle: impl Fn(tiny_http::Request)) -> io::Result<()> {
+        self.is_running.store(true, Ordering::SeqCst);
+        info!("{} is running on {}", self.name, self.address);
+
+        while self.is_running.load(Ordering::SeqCst) {
+            match self.server.recv_timeout(Duration::from_millis(100)) {
+                Ok(Some(request)) => handle(request),
+                Ok(None) => {}
+                Err(e) => error!("{} failed to receive a request: {}", self.name, e),
+            }
+        }
+
+        info!("{} stopped.", self.name);
+        Ok(())
+    }
+
+    /// Stops the listener by setting the `is_running` flag to `false`
+    pub(crate) fn stop(&self) {
+        if self.is_running.swap(false, Ordering::SeqCst) {
+            info!("Shutdown signal sent to {}.", self.name.to_lowercase());
+        } else {
+            warn!("{} was already stopped or not running.", self.name);
+        }
+    }
+}
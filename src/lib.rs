@@ -1,4 +1,35 @@
+pub mod admin;
+#[cfg(feature = "async")]
+pub mod async_client;
+pub mod audit;
+pub mod auth;
+pub mod client;
+pub mod connection;
+pub mod filter;
+pub mod framing;
+pub mod handler;
+#[cfg(unix)]
+pub mod handoff;
+pub mod health;
+#[cfg(feature = "http")]
+pub mod http;
+#[cfg(feature = "http")]
+mod http_server;
+pub mod listener;
+pub mod metrics;
+pub mod policy;
+pub mod pool;
+#[cfg(feature = "record")]
+pub mod recorder;
 pub mod server;
+#[cfg(unix)]
+pub mod systemd;
+#[cfg(feature = "tls")]
+pub mod tls;
+pub mod udp;
+pub mod validation;
+#[cfg(feature = "websocket")]
+pub mod websocket;
 
 pub mod message {
     include!(concat!(env!("OUT_DIR"), "/messages.rs"));
@@ -0,0 +1,737 @@
+use std::{
+    fmt,
+    io::{self, Read, Write},
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+use tracing::{info, warn};
+
+use socket2::{Domain, Protocol, SockRef, Socket, TcpKeepalive, Type};
+
+#[cfg(feature = "websocket")]
+use crate::websocket::WebSocketStream;
+#[cfg(feature = "tls")]
+use rustls::{ServerConfig, ServerConnection, StreamOwned};
+#[cfg(feature = "tls")]
+use std::sync::Arc;
+#[cfg(unix)]
+use std::{
+    fs,
+    os::unix::{
+        fs::{DirBuilderExt, FileTypeExt, PermissionsExt},
+        io::{AsRawFd, OwnedFd, RawFd},
+        net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+};
+
+/// Options applied when binding a Unix domain socket listener
+#[cfg(unix)]
+#[derive(Debug, Clone)]
+pub struct UnixSocketConfig {
+    /// Remove a leftover socket file from a previous run before binding
+    pub remove_stale: bool,
+    /// File mode of the socket file (e.g. `0o660`), set before the socket
+    /// appears at its path
+    pub mode: Option<u32>,
+}
+
+#[cfg(unix)]
+impl Default for UnixSocketConfig {
+    fn default() -> Self {
+        UnixSocketConfig {
+            remove_stale: true,
+            mode: None,
+        }
+    }
+}
+
+/// Socket options applied to a TCP listener when it is bound
+#[derive(Debug, Clone)]
+pub struct ListenerOptions {
+    /// SO_REUSEADDR, lets a restarted server bind while old connections linger in TIME_WAIT
+    pub reuse_address: bool,
+    /// SO_REUSEPORT, lets several sockets bind the same port (Unix only)
+    pub reuse_port: bool,
+    /// Maximum length of the queue of pending connections
+    pub backlog: i32,
+    /// IPV6_V6ONLY for IPv6 addresses; `None` keeps the system default
+    pub only_v6: Option<bool>,
+}
+
+impl Default for ListenerOptions {
+    fn default() -> Self {
+        ListenerOptions {
+            reuse_address: true,
+            reuse_port: false,
+            backlog: 128,
+            only_v6: None,
+        }
+    }
+}
+
+/// TCP keepalive probing, used to detect peers that vanished without closing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeepaliveOptions {
+    /// Idle time before the first probe is sent
+    pub time: Duration,
+    /// Time between unanswered probes
+    pub interval: Option<Duration>,
+    /// Unanswered probes before the connection is dropped
+    pub retries: Option<u32>,
+}
+
+/// Socket options applied to every accepted TCP connection.
+///
+/// Options left as `None` keep the system default.
+#[derive(Debug, Clone, Default)]
+pub struct StreamOptions {
+    /// TCP_NODELAY, disables Nagle's algorithm for lower request/response latency
+    pub nodelay: Option<bool>,
+    pub keepalive: Option<KeepaliveOptions>,
+    /// SO_SNDBUF in bytes
+    pub send_buffer_size: Option<usize>,
+    /// SO_RCVBUF in bytes
+    pub recv_buffer_size: Option<usize>,
+}
+
+impl StreamOptions {
+    /// Applies the options to an accepted connection
+    pub fn apply(&self, stream: &TcpStream) -> io::Result<()> {
+        let socket = SockRef::from(stream);
+        if let Some(nodelay) = self.nodelay {
+            socket.set_nodelay(nodelay)?;
+        }
+        if let Some(keepalive) = self.keepalive {
+            #[allow(unused_mut)]
+            let mut params = TcpKeepalive::new().with_time(keepalive.time);
+            #[cfg(any(target_os = "linux", target_os = "android", target_os = "macos"))]
+            {
+                if let Some(interval) = keepalive.interval {
+                    params = params.with_interval(interval);
+                }
+                if let Some(retries) = keepalive.retries {
+                    params = params.with_retries(retries);
+                }
+            }
+            socket.set_tcp_keepalive(&params)?;
+        }
+        if let Some(size) = self.send_buffer_size {
+            socket.set_send_buffer_size(size)?;
+        }
+        if let Some(size) = self.recv_buffer_size {
+            socket.set_recv_buffer_size(size)?;
+        }
+        Ok(())
+    }
+}
+
+/// Credentials of the process on the other end of a Unix domain socket
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeerCredentials {
+    pub pid: Option<u32>,
+    pub uid: u32,
+    pub gid: u32,
+}
+
+/// Address of the remote end of an accepted connection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PeerAddr {
+    Tcp(SocketAddr),
+    /// Unix peers are usually unnamed, in which case the path is `None`
+    #[cfg(unix)]
+    Unix(Option<PathBuf>),
+}
+
+impl fmt::Display for PeerAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PeerAddr::Tcp(addr) => write!(f, "{}", addr),
+            #[cfg(unix)]
+            PeerAddr::Unix(Some(path)) => write!(f, "unix:{}", path.display()),
+            #[cfg(unix)]
+            PeerAddr::Unix(None) => write!(f, "unix:(unnamed)"),
+        }
+    }
+}
+
+/// Metadata describing an accepted connection
+#[derive(Debug, Clone)]
+pub struct ConnectionInfo {
+    pub peer: PeerAddr,
+    /// Peer process credentials (SO_PEERCRED), only available for Unix sockets
+    pub credentials: Option<PeerCredentials>,
+}
+
+impl fmt::Display for ConnectionInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.peer)?;
+        if let Some(creds) = self.credentials {
+            write!(f, " (uid={}, gid={}", creds.uid, creds.gid)?;
+            if let Some(pid) = creds.pid {
+                write!(f, ", pid={}", pid)?;
+            }
+            write!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+
+/// A listening socket the server accepts connections from
+pub enum Listener {
+    Tcp(TcpListener),
+    /// TCP listener whose accepted connections are wrapped in TLS
+    #[cfg(feature = "tls")]
+    Tls(TcpListener, Arc<ServerConfig>),
+    /// TCP listener speaking WebSocket, one protobuf frame per binary message
+    #[cfg(feature = "websocket")]
+    WebSocket(TcpListener),
+    /// Unix domain socket listener, along with the socket file to remove when
+    /// it is dropped. Adopted sockets have no path, their owner cleans up.
+    #[cfg(unix)]
+    Unix(UnixListener, Option<PathBuf>),
+}
+
+impl Listener {
+    /// Binds a TCP listener to the given address
+    pub fn bind_tcp(addr: &str) -> io::Result<Self> {
+        Ok(Listener::Tcp(TcpListener::bind(addr)?))
+    }
+
+    /// Binds a TCP listener to the given address with explicit socket options.
+    ///
+    /// Like `TcpListener::bind`, every resolved address is tried in turn.
+    pub fn bind_tcp_with(addr: &str, options: &ListenerOptions) -> io::Result<Self> {
+        let mut last_error = None;
+        for addr in addr.to_socket_addrs()? {
+            match bind_socket(addr, options) {
+                Ok(listener) => return Ok(Listener::Tcp(listener)),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "could not resolve to any addresses",
+            )
+        }))
+    }
+
+    /// Binds a TCP listener on every interface that accepts both IPv6 and
+    /// IPv4 (as IPv4-mapped addresses) connections on the same port
+    pub fn bind_dual_stack(port: u16) -> io::Result<Self> {
+        let options = ListenerOptions {
+            // Don't rely on the system default (net.ipv6.bindv6only on Linux)
+            only_v6: Some(false),
+            ..Default::default()
+        };
+        Self::bind_tcp_with(&format!("[::]:{}", port), &options)
+    }
+
+    /// Binds `count` TCP listeners sharing one port through SO_REUSEPORT, so
+    /// the kernel spreads incoming connections across them
+    #[cfg(unix)]
+    pub fn bind_reuseport(
+        addr: &str,
+        count: usize,
+        options: &ListenerOptions,
+    ) -> io::Result<Vec<Self>> {
+        let options = ListenerOptions {
+            reuse_port: true,
+            ..options.clone()
+        };
+
+        let mut listeners = Vec::with_capacity(count);
+        if count == 0 {
+            return Ok(listeners);
+        }
+        let first = Self::bind_tcp_with(addr, &options)?;
+        // Port 0 picks a free port for the first socket; the rest must join it
+        let bound = first.local_addr()?;
+        listeners.push(first);
+        for _ in 1..count {
+            listeners.push(Self::bind_tcp_with(&bound, &options)?);
+        }
+        Ok(listeners)
+    }
+
+    /// Binds a TCP listener that serves TLS with the given configuration
+    #[cfg(feature = "tls")]
+    pub fn bind_tls(addr: &str, config: Arc<ServerConfig>) -> io::Result<Self> {
+        Ok(Listener::Tls(TcpListener::bind(addr)?, config))
+    }
+
+    /// Binds a TCP listener that accepts WebSocket connections
+    #[cfg(feature = "websocket")]
+    pub fn bind_websocket(addr: &str) -> io::Result<Self> {
+        Ok(Listener::WebSocket(TcpListener::bind(addr)?))
+    }
+
+    /// Binds a Unix domain socket listener to the given path
+    #[cfg(unix)]
+    pub fn bind_unix<P: AsRef<Path>>(path: P, config: &UnixSocketConfig) -> io::Result<Self> {
+        let path = path.as_ref();
+        if config.remove_stale {
+            remove_stale_socket(path)?;
+        }
+
+        let listener = match config.mode {
+            Some(mode) => bind_unix_with_mode(path, mode)?,
+            None => UnixListener::bind(path)?,
+        };
+        Ok(Listener::Unix(listener, Some(path.to_path_buf())))
+    }
+
+    /// Adopts an already bound and listening socket, e.g. one inherited from
+    /// systemd or handed over by another process.
+    ///
+    /// TCP sockets become `Listener::Tcp` and Unix sockets `Listener::Unix`.
+    /// The socket file of an adopted Unix socket is left in place on drop.
+    #[cfg(unix)]
+    pub fn from_fd(fd: OwnedFd) -> io::Result<Self> {
+        let socket = Socket::from(fd);
+        if socket.r#type()? != Type::STREAM {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "inherited socket is not a stream socket",
+            ));
+        }
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        if !socket.is_listener()? {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "inherited socket is not listening",
+            ));
+        }
+
+        let addr = socket.local_addr()?;
+        if addr.as_socket().is_some() {
+            Ok(Listener::Tcp(socket.into()))
+        } else if addr.domain() == Domain::UNIX {
+            Ok(Listener::Unix(OwnedFd::from(socket).into(), None))
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "inherited socket is neither TCP nor a Unix socket",
+            ))
+        }
+    }
+
+    /// Returns a printable form of the bound endpoint
+    pub fn local_addr(&self) -> io::Result<String> {
+        match self {
+            Listener::Tcp(listener) => Ok(listener.local_addr()?.to_string()),
+            #[cfg(feature = "tls")]
+            Listener::Tls(listener, _) => Ok(listener.local_addr()?.to_string()),
+            #[cfg(feature = "websocket")]
+            Listener::WebSocket(listener) => Ok(format!("ws://{}", listener.local_addr()?)),
+            #[cfg(unix)]
+            // A socket bound with a mode was bound elsewhere and linked in
+            Listener::Unix(_, Some(path)) => Ok(PeerAddr::Unix(Some(path.clone())).to_string()),
+            Listener::Unix(listener, None) => Ok(PeerAddr::Unix(
+                listener.local_addr()?.as_pathname().map(Path::to_path_buf),
+            )
+            .to_string()),
+        }
+    }
+
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        match self {
+            Listener::Tcp(listener) => listener.set_nonblocking(nonblocking),
+            #[cfg(feature = "tls")]
+            Listener::Tls(listener, _) => listener.set_nonblocking(nonblocking),
+            #[cfg(feature = "websocket")]
+            Listener::WebSocket(listener) => listener.set_nonblocking(nonblocking),
+            #[cfg(unix)]
+            Listener::Unix(listener, _) => listener.set_nonblocking(nonblocking),
+        }
+    }
+
+    /// Waits up to `timeout` for a connection to be ready to accept.
+    ///
+    /// Returns `false` on timeout. Lets a non-blocking accept loop sleep until
+    /// a client arrives instead of polling on a fixed interval.
+    #[cfg(unix)]
+    pub fn wait_for_connection(&self, timeout: Duration) -> io::Result<bool> {
+        let mut pollfd = libc::pollfd {
+            fd: self.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let timeout_ms = libc::c_int::try_from(timeout.as_millis()).unwrap_or(libc::c_int::MAX);
+        // SAFETY: `pollfd` is a valid array of one entry for the duration of the call
+        match unsafe { libc::poll(&mut pollfd, 1, timeout_ms) } {
+            -1 => {
+                let e = io::Error::last_os_error();
+                if e.kind() == io::ErrorKind::Interrupted {
+                    Ok(false)
+                } else {
+                    Err(e)
+                }
+            }
+            ready => Ok(ready > 0),
+        }
+    }
+
+    /// Waits up to `timeout` for a connection to be ready to accept
+    #[cfg(not(unix))]
+    pub fn wait_for_connection(&self, timeout: Duration) -> io::Result<bool> {
+        std::thread::sleep(timeout);
+        Ok(true)
+    }
+
+    /// Leaves the socket file in place when the listener is dropped, e.g.
+    /// because the socket was handed over to another process
+    #[cfg(unix)]
+    pub fn keep_socket_file(&mut self) {
+        if let Listener::Unix(_, path) = self {
+            *path = None;
+        }
+    }
+
+    /// Accepts a new connection along with its metadata
+    pub fn accept(&self) -> io::Result<(Stream, ConnectionInfo)> {
+        self.accept_with(&StreamOptions::default())
+    }
+
+    /// Accepts a new connection, applying `options` to TCP-based streams
+    pub fn accept_with(&self, options: &StreamOptions) -> io::Result<(Stream, ConnectionInfo)> {
+        match self {
+            Listener::Tcp(listener) => {
+                let (stream, addr) = listener.accept()?;
+                // Accepted sockets may inherit non-blocking mode on some platforms
+                stream.set_nonblocking(false)?;
+                if let Err(e) = options.apply(&stream) {
+                    warn!("Failed to set socket options for {}: {}", addr, e);
+                }
+                let info = ConnectionInfo {
+                    peer: PeerAddr::Tcp(addr),
+                    credentials: None,
+                };
+                Ok((Stream::Tcp(stream), info))
+            }
+            #[cfg(feature = "tls")]
+            Listener::Tls(listener, config) => {
+                let (stream, addr) = listener.accept()?;
+                stream.set_nonblocking(false)?;
+                if let Err(e) = options.apply(&stream) {
+                    warn!("Failed to set socket options for {}: {}", addr, e);
+                }
+                // The handshake runs lazily on the first read, off the accept loop
+                let conn = ServerConnection::new(config.clone()).map_err(io::Error::other)?;
+                let info = ConnectionInfo {
+                    peer: PeerAddr::Tcp(addr),
+                    credentials: None,
+                };
+                Ok((Stream::Tls(Box::new(StreamOwned::new(conn, stream))), info))
+            }
+            #[cfg(feature = "websocket")]
+            Listener::WebSocket(listener) => {
+                let (stream, addr) = listener.accept()?;
+                stream.set_nonblocking(false)?;
+                if let Err(e) = options.apply(&stream) {
+                    warn!("Failed to set socket options for {}: {}", addr, e);
+                }
+                let info = ConnectionInfo {
+                    peer: PeerAddr::Tcp(addr),
+                    credentials: None,
+                };
+                Ok((
+                    Stream::WebSocket(Box::new(WebSocketStream::new(stream))),
+                    info,
+                ))
+            }
+            #[cfg(unix)]
+            Listener::Unix(listener, _) => {
+                let (stream, addr) = listener.accept()?;
+                stream.set_nonblocking(false)?;
+                let credentials = peer_credentials(&stream)
+                    .map_err(|e| warn!("Failed to read peer credentials: {}", e))
+                    .ok();
+                let info = ConnectionInfo {
+                    peer: PeerAddr::Unix(addr.as_pathname().map(Path::to_path_buf)),
+                    credentials,
+                };
+                Ok((Stream::Unix(stream), info))
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+impl AsRawFd for Listener {
+    fn as_raw_fd(&self) -> RawFd {
+        match self {
+            Listener::Tcp(listener) => listener.as_raw_fd(),
+            #[cfg(feature = "tls")]
+            Listener::Tls(listener, _) => listener.as_raw_fd(),
+            #[cfg(feature = "websocket")]
+            Listener::WebSocket(listener) => listener.as_raw_fd(),
+            Listener::Unix(listener, _) => listener.as_raw_fd(),
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Listener {
+    fn drop(&mut self) {
+        // Unix sockets leave their file behind; clean it up once we stop listening
+        if let Listener::Unix(_, Some(path)) = self {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// A connected byte stream, independent of the underlying transport
+pub enum Stream {
+    Tcp(TcpStream),
+    #[cfg(feature = "tls")]
+    Tls(Box<StreamOwned<ServerConnection, TcpStream>>),
+    #[cfg(feature = "websocket")]
+    WebSocket(Box<WebSocketStream>),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl Stream {
+    /// Returns a handle that can close this connection from another thread
+    pub fn closer(&self) -> io::Result<StreamCloser> {
+        match self {
+            Stream::Tcp(stream) => Ok(StreamCloser::Tcp(stream.try_clone()?)),
+            #[cfg(feature = "tls")]
+            Stream::Tls(stream) => Ok(StreamCloser::Tcp(stream.sock.try_clone()?)),
+            #[cfg(feature = "websocket")]
+            Stream::WebSocket(stream) => Ok(StreamCloser::Tcp(stream.try_clone_socket()?)),
+            #[cfg(unix)]
+            Stream::Unix(stream) => Ok(StreamCloser::Unix(stream.try_clone()?)),
+        }
+    }
+
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.set_read_timeout(timeout),
+            #[cfg(feature = "tls")]
+            Stream::Tls(stream) => stream.sock.set_read_timeout(timeout),
+            // The timeout belongs to the socket, so setting it on a clone works
+            #[cfg(feature = "websocket")]
+            Stream::WebSocket(stream) => stream.try_clone_socket()?.set_read_timeout(timeout),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.set_read_timeout(timeout),
+        }
+    }
+
+    pub fn shutdown(&mut self, how: Shutdown) -> io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.shutdown(how),
+            #[cfg(feature = "tls")]
+            Stream::Tls(stream) => {
+                stream.conn.send_close_notify();
+                let _ = stream.flush();
+                stream.sock.shutdown(how)
+            }
+            #[cfg(feature = "websocket")]
+            Stream::WebSocket(stream) => stream.shutdown(how),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.shutdown(how),
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.read(buf),
+            #[cfg(feature = "tls")]
+            Stream::Tls(stream) => stream.read(buf),
+            #[cfg(feature = "websocket")]
+            Stream::WebSocket(stream) => stream.read(buf),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.write(buf),
+            #[cfg(feature = "tls")]
+            Stream::Tls(stream) => stream.write(buf),
+            #[cfg(feature = "websocket")]
+            Stream::WebSocket(stream) => stream.write(buf),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.flush(),
+            #[cfg(feature = "tls")]
+            Stream::Tls(stream) => stream.flush(),
+            #[cfg(feature = "websocket")]
+            Stream::WebSocket(stream) => stream.flush(),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.flush(),
+        }
+    }
+}
+
+/// Closes a connection from outside the thread serving it.
+///
+/// The socket is shut down in both directions, so a blocked read on the
+/// connection returns and its thread finishes.
+pub enum StreamCloser {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl StreamCloser {
+    pub fn close(&self) -> io::Result<()> {
+        let result = match self {
+            StreamCloser::Tcp(stream) => stream.shutdown(Shutdown::Both),
+            #[cfg(unix)]
+            StreamCloser::Unix(stream) => stream.shutdown(Shutdown::Both),
+        };
+        // The peer may have gone away already
+        match result {
+            Err(e) if e.kind() == io::ErrorKind::NotConnected => Ok(()),
+            result => result,
+        }
+    }
+}
+
+fn bind_socket(addr: SocketAddr, options: &ListenerOptions) -> io::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    if addr.is_ipv6() {
+        if let Some(only_v6) = options.only_v6 {
+            socket.set_only_v6(only_v6)?;
+        }
+    }
+    socket.set_reuse_address(options.reuse_address)?;
+    if options.reuse_port {
+        #[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))]
+        socket.set_reuse_port(true)?;
+        #[cfg(not(all(unix, not(any(target_os = "solaris", target_os = "illumos")))))]
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "SO_REUSEPORT is not supported on this platform",
+        ));
+    }
+    socket.bind(&addr.into())?;
+    socket.listen(options.backlog)?;
+    Ok(socket.into())
+}
+
+/// Removes a socket file left behind by a previous run.
+///
+/// A socket that still accepts connections belongs to a live server, so it is
+/// left alone and `AddrInUse` is returned instead.
+#[cfg(unix)]
+fn remove_stale_socket(path: &Path) -> io::Result<()> {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+
+    if !metadata.file_type().is_socket() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} exists and is not a socket", path.display()),
+        ));
+    }
+
+    match UnixStream::connect(path) {
+        Ok(_) => Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            format!("{} is in use by another server", path.display()),
+        )),
+        Err(_) => {
+            info!("Removing stale socket {}", path.display());
+            fs::remove_file(path)
+        }
+    }
+}
+
+/// Binds a Unix socket at `path` that is never reachable with a looser
+/// mode than `mode`: it is bound inside a private directory, given its mode
+/// and only then linked into place.
+#[cfg(unix)]
+fn bind_unix_with_mode(path: &Path, mode: u32) -> io::Result<UnixListener> {
+    let name = path.file_name().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a socket path", path.display()),
+        )
+    })?;
+    let mut private_name = std::ffi::OsString::from(".");
+    private_name.push(name);
+    private_name.push(format!(".{}.bind", std::process::id()));
+    let private_dir = path.with_file_name(private_name);
+    fs::DirBuilder::new().mode(0o700).create(&private_dir)?;
+
+    let private_path = private_dir.join("socket");
+    let bound = UnixListener::bind(&private_path).and_then(|listener| {
+        fs::set_permissions(&private_path, fs::Permissions::from_mode(mode))?;
+        // Unlike a rename, linking fails rather than replace an existing file
+        fs::hard_link(&private_path, path).map_err(|e| match e.kind() {
+            io::ErrorKind::AlreadyExists => io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!("{} already exists", path.display()),
+            ),
+            _ => e,
+        })?;
+        Ok(listener)
+    });
+    if let Err(e) = fs::remove_dir_all(&private_dir) {
+        warn!("Failed to remove {}: {}", private_dir.display(), e);
+    }
+    bound
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn peer_credentials(stream: &UnixStream) -> io::Result<PeerCredentials> {
+    let mut cred = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    // SAFETY: `cred` and `len` are valid for writes and sized for SO_PEERCRED
+    let ret = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut cred as *mut libc::ucred as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if ret != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(PeerCredentials {
+        pid: u32::try_from(cred.pid).ok().filter(|pid| *pid != 0),
+        uid: cred.uid,
+        gid: cred.gid,
+    })
+}
+
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+fn peer_credentials(stream: &UnixStream) -> io::Result<PeerCredentials> {
+    let mut uid: libc::uid_t = 0;
+    let mut gid: libc::gid_t = 0;
+    // SAFETY: `uid` and `gid` are valid for writes
+    if unsafe { libc::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(PeerCredentials {
+        pid: None,
+        uid,
+        gid,
+    })
+}
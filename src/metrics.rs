@@ -0,0 +1,344 @@
+//! Server metrics, readable as a snapshot or in the Prometheus text format.
+
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Write as _},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "http")]
+use crate::http_server::HttpServer;
+#[cfg(feature = "http")]
+use std::{io, sync::Arc};
+#[cfg(feature = "http")]
+use tracing::error;
+
+/// Upper bounds of the handler latency histogram buckets, in seconds
+pub const LATENCY_BUCKETS: &[f64] = &[
+    0.000_01, 0.000_025, 0.000_05, 0.000_1, 0.000_25, 0.000_5, 0.001, 0.002_5, 0.005, 0.01, 0.025,
+    0.05, 0.1, 0.25, 0.5, 1.0,
+];
+
+/// Live counters updated by the server as it runs
+#[derive(Debug)]
+pub struct Metrics {
+    started: Instant,
+    connections_accepted: AtomicU64,
+    connections_closed: AtomicU64,
+    connections_rejected: AtomicU64,
+    requests: Mutex<BTreeMap<&'static str, u64>>,
+    decode_errors: AtomicU64,
+    bytes_received: AtomicU64,
+    bytes_sent: AtomicU64,
+    handler_latency: Histogram,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics {
+            started: Instant::now(),
+            connections_accepted: AtomicU64::new(0),
+            connections_closed: AtomicU64::new(0),
+            connections_rejected: AtomicU64::new(0),
+            requests: Mutex::new(BTreeMap::new()),
+            decode_errors: AtomicU64::new(0),
+            bytes_received: AtomicU64::new(0),
+            bytes_sent: AtomicU64::new(0),
+            handler_latency: Histogram::new(LATENCY_BUCKETS),
+        }
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn connection_accepted(&self) {
+        self.connections_accepted.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn connection_closed(&self) {
+        self.connections_closed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts a connection refused right after being accepted
+    pub fn connection_rejected(&self) {
+        self.connections_rejected.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts a request of the given message type and how long it took to handle
+    pub fn request_handled(&self, message_type: &'static str, latency: Duration) {
+        *self
+            .requests
+            .lock()
+            .unwrap()
+            .entry(message_type)
+            .or_default() += 1;
+        self.handler_latency.observe(latency);
+    }
+
+    pub fn decode_error(&self) {
+        self.decode_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn bytes_received(&self, count: usize) {
+        self.bytes_received
+            .fetch_add(count as u64, Ordering::Relaxed);
+    }
+
+    pub fn bytes_sent(&self, count: usize) {
+        self.bytes_sent.fetch_add(count as u64, Ordering::Relaxed);
+    }
+
+    /// Returns a copy of the current values
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let accepted = self.connections_accepted.load(Ordering::Relaxed);
+        let closed = self.connections_closed.load(Ordering::Relaxed);
+        MetricsSnapshot {
+            uptime: self.started.elapsed(),
+            connections_accepted: accepted,
+            // Closed is read after accepted, so it can only lag behind
+            connections_active: accepted.saturating_sub(closed),
+            connections_closed: closed,
+            connections_rejected: self.connections_rejected.load(Ordering::Relaxed),
+            requests: self
+                .requests
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(message_type, count)| (message_type.to_string(), *count))
+                .collect(),
+            decode_errors: self.decode_errors.load(Ordering::Relaxed),
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            handler_latency: self.handler_latency.snapshot(),
+        }
+    }
+}
+
+/// Cumulative histogram with fixed buckets
+#[derive(Debug)]
+struct Histogram {
+    bounds: &'static [f64],
+    counts: Vec<AtomicU64>,
+    count: AtomicU64,
+    sum_nanos: AtomicU64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Self {
+        Histogram {
+            bounds,
+            counts: bounds.iter().map(|_| AtomicU64::new(0)).collect(),
+            count: AtomicU64::new(0),
+            sum_nanos: AtomicU64::new(0),
+        }
+    }
+
+    fn observe(&self, value: Duration) {
+        let seconds = value.as_secs_f64();
+        if let Some(bucket) = self.bounds.iter().position(|bound| seconds <= *bound) {
+            self.counts[bucket].fetch_add(1, Ordering::Relaxed);
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_nanos
+            .fetch_add(value.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> HistogramSnapshot {
+        let mut cumulative = 0;
+        let buckets = self
+            .bounds
+            .iter()
+            .zip(&self.counts)
+            .map(|(bound, count)| {
+                cumulative += count.load(Ordering::Relaxed);
+                (*bound, cumulative)
+            })
+            .collect();
+        HistogramSnapshot {
+            buckets,
+            count: self.count.load(Ordering::Relaxed),
+            sum: Duration::from_nanos(self.sum_nanos.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// Point-in-time copy of a histogram
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistogramSnapshot {
+    /// `(upper bound in seconds, observations at or below it)`, cumulative
+    pub buckets: Vec<(f64, u64)>,
+    pub count: u64,
+    pub sum: Duration,
+}
+
+/// Point-in-time copy of the server metrics
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetricsSnapshot {
+    /// Time since the metrics, and so the server, were created
+    pub uptime: Duration,
+    pub connections_accepted: u64,
+    pub connections_active: u64,
+    pub connections_closed: u64,
+    /// Connections refused by the IP filter; not counted as accepted
+    pub connections_rejected: u64,
+    /// Handled requests by message type (e.g. `add_request`)
+    pub requests: BTreeMap<String, u64>,
+    pub decode_errors: u64,
+    pub bytes_received: u64,
+    pub bytes_sent: u64,
+    pub handler_latency: HistogramSnapshot,
+}
+
+impl MetricsSnapshot {
+    /// Renders the metrics in the Prometheus text exposition format
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        self.write_prometheus(&mut out)
+            .expect("writing to a String cannot fail");
+        out
+    }
+
+    fn write_prometheus(&self, out: &mut String) -> fmt::Result {
+        let counters = [
+            (
+                "server_connections_accepted_total",
+                "Connections accepted.",
+                self.connections_accepted,
+            ),
+            (
+                "server_connections_closed_total",
+                "Connections closed.",
+                self.connections_closed,
+            ),
+            (
+                "server_connections_rejected_total",
+                "Connections refused by the IP filter.",
+                self.connections_rejected,
+            ),
+            (
+                "server_decode_errors_total",
+                "Requests that could not be decoded.",
+                self.decode_errors,
+            ),
+            (
+                "server_received_bytes_total",
+                "Bytes read from clients.",
+                self.bytes_received,
+            ),
+            (
+                "server_sent_bytes_total",
+                "Bytes written to clients.",
+                self.bytes_sent,
+            ),
+        ];
+        for (name, help, value) in counters {
+            writeln!(out, "# HELP {} {}", name, help)?;
+            writeln!(out, "# TYPE {} counter", name)?;
+            writeln!(out, "{} {}", name, value)?;
+        }
+
+        writeln!(
+            out,
+            "# HELP server_connections_active Connections currently open."
+        )?;
+        writeln!(out, "# TYPE server_connections_active gauge")?;
+        writeln!(out, "server_connections_active {}", self.connections_active)?;
+
+        writeln!(
+            out,
+            "# HELP server_uptime_seconds Time since the server was created."
+        )?;
+        writeln!(out, "# TYPE server_uptime_seconds gauge")?;
+        writeln!(out, "server_uptime_seconds {}", self.uptime.as_secs_f64())?;
+
+        writeln!(
+            out,
+            "# HELP server_requests_total Requests handled, by message type."
+        )?;
+        writeln!(out, "# TYPE server_requests_total counter")?;
+        for (message_type, count) in &self.requests {
+            writeln!(
+                out,
+                "server_requests_total{{type=\"{}\"}} {}",
+                message_type, count
+            )?;
+        }
+
+        let name = "server_handler_duration_seconds";
+        writeln!(out, "# HELP {} Time spent handling a request.", name)?;
+        writeln!(out, "# TYPE {} histogram", name)?;
+        for (bound, count) in &self.handler_latency.buckets {
+            writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, bound, count)?;
+        }
+        writeln!(
+            out,
+            "{}_bucket{{le=\"+Inf\"}} {}",
+            name, self.handler_latency.count
+        )?;
+        writeln!(
+            out,
+            "{}_sum {}",
+            name,
+            self.handler_latency.sum.as_secs_f64()
+        )?;
+        writeln!(out, "{}_count {}", name, self.handler_latency.count)
+    }
+}
+
+/// HTTP listener serving `GET /metrics` in the Prometheus text format
+#[cfg(feature = "http")]
+pub struct MetricsExporter {
+    server: HttpServer,
+    metrics: Arc<Metrics>,
+}
+
+#[cfg(feature = "http")]
+impl MetricsExporter {
+    /// Creates a new exporter bound to `addr`, serving `metrics`
+    pub fn new(addr: &str, metrics: Arc<Metrics>) -> io::Result<Self> {
+        Ok(MetricsExporter {
+            server: HttpServer::bind(addr, "Metrics exporter")?,
+            metrics,
+        })
+    }
+
+    /// Returns the exporter's address
+    pub fn address(&self) -> &str {
+        self.server.address()
+    }
+
+    /// Runs the exporter, answering scrapes until `stop()` is called
+    pub fn run(&self) -> io::Result<()> {
+        self.server.run(|request| self.handle(request))
+    }
+
+    /// Stops the exporter
+    pub fn stop(&self) {
+        self.server.stop();
+    }
+
+    fn handle(&self, request: tiny_http::Request) {
+        use tiny_http::{Header, Method, Response};
+
+        let path = request.url().split('?').next().unwrap_or_default();
+        let response = if path != "/metrics" {
+            Response::from_string(format!("no route for {}\n", path)).with_status_code(404)
+        } else if *request.method() != Method::Get {
+            Response::from_string("/metrics requires GET\n").with_status_code(405)
+        } else {
+            let content_type = Header::from_bytes("Content-Type", "text/plain; version=0.0.4")
+                .expect("static header is valid");
+            Response::from_string(self.metrics.snapshot().to_prometheus()).with_header(content_type)
+        };
+        if let Err(e) = request.respond(response) {
+            error!("Failed to send metrics response: {}", e);
+        }
+    }
+}
@@ -0,0 +1,156 @@
+//! Authorization of message types per identity.
+//!
+//! A `Policy` (see `Server::with_policy`) lists the `ClientMessage` variants,
+//! by their names in `handler::REQUEST_TYPES`, that each authenticated
+//! identity may send, directly or through roles. Anything not allowed is
+//! denied, including every request of unauthenticated connections, so a
+//! policy goes together with `Server::with_credentials`. Authentication and
+//! health checks are always allowed.
+
+use crate::handler::REQUEST_TYPES;
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs,
+    io::{self, ErrorKind},
+    path::Path,
+};
+
+/// Allows every message type when used in a role or grant
+pub const ANY: &str = "*";
+
+/// Message types every connection may send
+const ALWAYS_ALLOWED: &[&str] = &[
+    "auth_challenge_request",
+    "auth_request",
+    "health_check_request",
+    "none",
+];
+
+/// Message types allowed per role and per identity
+#[derive(Debug, Default)]
+pub struct Policy {
+    roles: HashMap<String, BTreeSet<String>>,
+    /// Roles assigned to each identity
+    members: HashMap<String, Vec<String>>,
+    /// Message types granted to identities directly
+    grants: HashMap<String, BTreeSet<String>>,
+}
+
+impl Policy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads a policy file with one rule per line:
+    ///
+    /// ```text
+    /// # comment
+    /// role <role> <message type>...
+    /// assign <identity> <role>...
+    /// allow <identity> <message type>...
+    /// ```
+    ///
+    /// `*` stands for every message type.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut policy = Self::new();
+        for (number, line) in fs::read_to_string(path)?.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let result = match words.as_slice() {
+                ["role", role, types @ ..] if !types.is_empty() => policy.with_role(role, types),
+                ["assign", identity, roles @ ..] if !roles.is_empty() => {
+                    Ok(policy.with_assignment(identity, roles))
+                }
+                ["allow", identity, types @ ..] if !types.is_empty() => {
+                    policy.with_grant(identity, types)
+                }
+                _ => Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    "expected `role <role> <type>...`, `assign <identity> <role>...` \
+                     or `allow <identity> <type>...`",
+                )),
+            };
+            policy = result
+                .map_err(|e| io::Error::new(e.kind(), format!("line {}: {}", number + 1, e)))?;
+        }
+
+        for (identity, roles) in &policy.members {
+            if let Some(role) = roles.iter().find(|role| !policy.roles.contains_key(*role)) {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("{} is assigned unknown role {}", identity, role),
+                ));
+            }
+        }
+        Ok(policy)
+    }
+
+    /// Defines a role allowed to send `types`. Fails on unknown message types.
+    pub fn with_role(mut self, role: &str, types: &[&str]) -> io::Result<Self> {
+        let types = check_types(types)?;
+        self.roles
+            .entry(role.to_string())
+            .or_default()
+            .extend(types);
+        Ok(self)
+    }
+
+    /// Gives `identity` the message types of `roles`
+    pub fn with_assignment(mut self, identity: &str, roles: &[&str]) -> Self {
+        self.members
+            .entry(identity.to_string())
+            .or_default()
+            .extend(roles.iter().map(|role| role.to_string()));
+        self
+    }
+
+    /// Allows `identity` to send `types`. Fails on unknown message types.
+    pub fn with_grant(mut self, identity: &str, types: &[&str]) -> io::Result<Self> {
+        let types = check_types(types)?;
+        self.grants
+            .entry(identity.to_string())
+            .or_default()
+            .extend(types);
+        Ok(self)
+    }
+
+    /// Whether `identity` (`None` if unauthenticated) may send a message of
+    /// `message_type`
+    pub fn allows(&self, identity: Option<&str>, message_type: &str) -> bool {
+        if ALWAYS_ALLOWED.contains(&message_type) {
+            return true;
+        }
+        let Some(identity) = identity else {
+            return false;
+        };
+        let permits =
+            |types: &BTreeSet<String>| types.contains(message_type) || types.contains(ANY);
+
+        self.grants.get(identity).is_some_and(permits)
+            || self.members.get(identity).is_some_and(|roles| {
+                roles
+                    .iter()
+                    .filter_map(|role| self.roles.get(role))
+                    .any(permits)
+            })
+    }
+}
+
+fn check_types(types: &[&str]) -> io::Result<Vec<String>> {
+    types
+        .iter()
+        .map(|name| {
+            if *name == ANY || REQUEST_TYPES.contains(name) {
+                Ok(name.to_string())
+            } else {
+                Err(io::Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("unknown message type {}", name),
+                ))
+            }
+        })
+        .collect()
+}
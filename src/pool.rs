@@ -0,0 +1,317 @@
+//! Pool of client connections shared between threads.
+//!
+//! ```no_run
+//! use embedded_recruitment_task::{client::Client, pool::{Pool, PoolConfig}};
+//!
+//! let addr = "127.0.0.1:8080".parse().unwrap();
+//! let pool = Pool::new(PoolConfig::default(), move || Client::new(addr))?;
+//! let sum = pool.get()?.add(2, 3)?;
+//! # Ok::<(), embedded_recruitment_task::client::Error>(())
+//! ```
+//!
+//! `Pool::get` hands out an idle connection, or opens one while there are
+//! fewer than `max_size`, or else waits for one to be returned. Connections
+//! go back to the pool when the `PooledClient` is dropped, unless they were
+//! lost. Connections idle for longer than `health_check_after` are checked
+//! with a health request before being handed out, and ones idle for longer
+//! than `idle_timeout` are closed, down to `min_size`, whenever the pool is
+//! used.
+//!
+//! `Pool::with_on_connect` prepares every connection the pool opens, e.g. by
+//! authenticating it, before it is handed out.
+
+use crate::client::{Client, Error, Result};
+use crate::message::{client_message, server_message, HealthCheckRequest};
+use std::{
+    collections::VecDeque,
+    io::{self, ErrorKind},
+    ops::{Deref, DerefMut},
+    sync::{Arc, Condvar, Mutex},
+    time::{Duration, Instant},
+};
+use tracing::{debug, warn};
+
+/// Sizes and timeouts of a `Pool`
+#[derive(Debug, Clone)]
+pub struct PoolConfig {
+    /// Connections opened up front and never closed for being idle
+    pub min_size: usize,
+    /// Most connections open at once, idle or checked out
+    pub max_size: usize,
+    /// How long `get` waits for a connection when all are checked out
+    pub checkout_timeout: Duration,
+    /// Idle connections beyond `min_size` are closed after this long
+    pub idle_timeout: Duration,
+    /// Connections idle for this long are health-checked before reuse
+    pub health_check_after: Duration,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        PoolConfig {
+            min_size: 1,
+            max_size: 8,
+            checkout_timeout: Duration::from_secs(5),
+            idle_timeout: Duration::from_secs(300),
+            health_check_after: Duration::from_secs(30),
+        }
+    }
+}
+
+struct Idle {
+    client: Client,
+    since: Instant,
+}
+
+struct State {
+    /// Oldest first; connections are reused newest first so that surplus
+    /// ones stay idle long enough to be evicted
+    idle: VecDeque<Idle>,
+    /// Connections open, idle or checked out, or being opened
+    open: usize,
+}
+
+/// Prepares a new connection, see `Pool::with_on_connect`
+type ConnectCallback = Arc<dyn Fn(&mut Client) -> Result<()> + Send + Sync>;
+
+struct Inner {
+    config: PoolConfig,
+    new_client: Box<dyn Fn() -> Client + Send + Sync>,
+    on_connect: Option<ConnectCallback>,
+    state: Mutex<State>,
+    /// Signalled when a connection is returned or closed
+    returned: Condvar,
+}
+
+/// Thread-safe pool of `Client` connections. Clones share the pool.
+#[derive(Clone)]
+pub struct Pool {
+    inner: Arc<Inner>,
+}
+
+impl Pool {
+    /// Creates a pool of clients made by `new_client`, e.g. with timeouts, TLS
+    /// or framing set, and opens `min_size` connections
+    pub fn new<F>(config: PoolConfig, new_client: F) -> Result<Self>
+    where
+        F: Fn() -> Client + Send + Sync + 'static,
+    {
+        Self::create(config, Box::new(new_client), None)
+    }
+
+    /// Like `new`, and runs `on_connect` on every connection the pool opens
+    /// before it is handed out, including after a client reconnects. It
+    /// replaces any `Client::on_connect` callback set by `new_client`.
+    pub fn with_on_connect<F, C>(config: PoolConfig, new_client: F, on_connect: C) -> Result<Self>
+    where
+        F: Fn() -> Client + Send + Sync + 'static,
+        C: Fn(&mut Client) -> Result<()> + Send + Sync + 'static,
+    {
+        Self::create(config, Box::new(new_client), Some(Arc::new(on_connect)))
+    }
+
+    fn create(
+        config: PoolConfig,
+        new_client: Box<dyn Fn() -> Client + Send + Sync>,
+        on_connect: Option<ConnectCallback>,
+    ) -> Result<Self> {
+        if config.max_size == 0 || config.min_size > config.max_size {
+            return Err(Error::Io(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "invalid pool size {}..={}",
+                    config.min_size, config.max_size
+                ),
+            )));
+        }
+
+        let inner = Inner {
+            state: Mutex::new(State {
+                idle: VecDeque::with_capacity(config.max_size),
+                open: 0,
+            }),
+            config,
+            new_client,
+            on_connect,
+            returned: Condvar::new(),
+        };
+        {
+            let mut state = inner.state.lock().unwrap();
+            for _ in 0..inner.config.min_size {
+                let client = inner.open()?;
+                state.idle.push_back(Idle {
+                    client,
+                    since: Instant::now(),
+                });
+                state.open += 1;
+            }
+        }
+        Ok(Pool {
+            inner: Arc::new(inner),
+        })
+    }
+
+    /// Checks out a connection, waiting up to `checkout_timeout` for one
+    pub fn get(&self) -> Result<PooledClient> {
+        let inner = &self.inner;
+        let deadline = Instant::now() + inner.config.checkout_timeout;
+        let mut state = inner.state.lock().unwrap();
+        loop {
+            let evicted = inner.evict_idle(&mut state);
+            if !evicted.is_empty() {
+                drop(state);
+                close(evicted);
+                state = inner.state.lock().unwrap();
+            }
+
+            if let Some(idle) = state.idle.pop_back() {
+                drop(state);
+                let mut client = idle.client;
+                if idle.since.elapsed() < inner.config.health_check_after || healthy(&mut client) {
+                    return Ok(self.checked_out(client));
+                }
+                debug!("Closing pooled connection that failed its health check");
+                close(vec![client]);
+                state = inner.state.lock().unwrap();
+                state.open -= 1;
+                continue;
+            }
+
+            if state.open < inner.config.max_size {
+                state.open += 1;
+                drop(state);
+                return match inner.open() {
+                    Ok(client) => Ok(self.checked_out(client)),
+                    Err(e) => {
+                        inner.closed();
+                        Err(e)
+                    }
+                };
+            }
+
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            if timeout.is_zero() {
+                return Err(Error::CheckoutTimeout);
+            }
+            state = inner.returned.wait_timeout(state, timeout).unwrap().0;
+        }
+    }
+
+    /// Number of open connections, idle or checked out
+    pub fn size(&self) -> usize {
+        self.inner.state.lock().unwrap().open
+    }
+
+    /// Number of idle connections
+    pub fn idle(&self) -> usize {
+        self.inner.state.lock().unwrap().idle.len()
+    }
+
+    fn checked_out(&self, client: Client) -> PooledClient {
+        PooledClient {
+            client: Some(client),
+            pool: self.inner.clone(),
+        }
+    }
+}
+
+impl Inner {
+    /// Makes a client and connects it
+    fn open(&self) -> Result<Client> {
+        let mut client = (self.new_client)();
+        if let Some(on_connect) = &self.on_connect {
+            let on_connect = on_connect.clone();
+            client = client.on_connect(move |client| on_connect(client));
+        }
+        client.connect()?;
+        Ok(client)
+    }
+
+    /// Takes the connections idle for longer than `idle_timeout`, keeping
+    /// `min_size` open
+    fn evict_idle(&self, state: &mut State) -> Vec<Client> {
+        let mut evicted = Vec::new();
+        while state.open > self.config.min_size
+            && state
+                .idle
+                .front()
+                .is_some_and(|idle| idle.since.elapsed() >= self.config.idle_timeout)
+        {
+            evicted.extend(state.idle.pop_front().map(|idle| idle.client));
+            state.open -= 1;
+        }
+        if !evicted.is_empty() {
+            debug!("Evicting {} idle pooled connection(s)", evicted.len());
+        }
+        evicted
+    }
+
+    /// Accounts for a connection that will not be returned
+    fn closed(&self) {
+        self.state.lock().unwrap().open -= 1;
+        self.returned.notify_one();
+    }
+}
+
+fn healthy(client: &mut Client) -> bool {
+    let request = client_message::Message::HealthCheckRequest(HealthCheckRequest {});
+    match client.request(request) {
+        Ok(server_message::Message::HealthCheckResponse(_)) => true,
+        Ok(other) => {
+            warn!("Unexpected health check response {:?}", other);
+            false
+        }
+        Err(e) => {
+            warn!("Pooled connection failed its health check: {}", e);
+            false
+        }
+    }
+}
+
+fn close(clients: Vec<Client>) {
+    for mut client in clients {
+        let _ = client.disconnect();
+    }
+}
+
+/// A connection checked out of a `Pool`, returned to it when dropped
+pub struct PooledClient {
+    client: Option<Client>,
+    pool: Arc<Inner>,
+}
+
+impl Deref for PooledClient {
+    type Target = Client;
+
+    fn deref(&self) -> &Client {
+        self.client
+            .as_ref()
+            .expect("client is present until dropped")
+    }
+}
+
+impl DerefMut for PooledClient {
+    fn deref_mut(&mut self) -> &mut Client {
+        self.client
+            .as_mut()
+            .expect("client is present until dropped")
+    }
+}
+
+impl Drop for PooledClient {
+    fn drop(&mut self) {
+        let Some(client) = self.client.take() else {
+            return;
+        };
+        // A lost connection is not worth keeping; `get` opens a new one
+        if !client.is_connected() {
+            self.pool.closed();
+            return;
+        }
+        self.pool.state.lock().unwrap().idle.push_back(Idle {
+            client,
+            since: Instant::now(),
+        });
+        self.pool.returned.notify_one();
+    }
+}
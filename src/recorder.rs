@@ -0,0 +1,237 @@
+//! Traffic recording and replay.
+//!
+//! A `Recorder` attached to a server (see `Server::with_recorder`) appends one
+//! JSON line per received message: the exact bytes, the decoded request, the
+//! response and when it happened. `replay` re-sends a recording to a server
+//! and reports every response that differs from the recorded one, so that a
+//! device's traffic can be reproduced after the fact.
+
+use crate::connection::ConnectionId;
+use crate::framing::{self, Framing};
+use crate::message::{auth_request::Credential, client_message, ClientMessage, ServerMessage};
+use prost::Message;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, ErrorKind, Read, Write},
+    net::TcpStream,
+    path::Path,
+    sync::Mutex,
+    time::Duration,
+};
+
+/// How long replay waits for a response where none was recorded
+const NO_RESPONSE_WAIT: Duration = Duration::from_millis(100);
+
+/// Recorded in place of a bearer token
+pub const REDACTED: &str = "<redacted>";
+
+/// One received message and what the server answered
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    /// When the message was received, in milliseconds since the Unix epoch
+    pub timestamp_ms: u64,
+    pub connection: ConnectionId,
+    pub peer: String,
+    /// The bytes received, hex encoded, so that undecodable input is kept too.
+    /// For an `AuthRequest` these are the bytes of the redacted request. A
+    /// length-delimited message is recorded without its length prefix.
+    pub raw: String,
+    /// The decoded request, absent if the bytes could not be decoded.
+    /// Credentials are redacted, so replayed authentications fail.
+    pub request: Option<ClientMessage>,
+    /// The response sent, if any
+    pub response: Option<ServerMessage>,
+    /// Time spent handling the request, in microseconds
+    pub latency_us: u64,
+}
+
+/// Appends records to a JSONL file
+pub struct Recorder {
+    file: Mutex<BufWriter<File>>,
+}
+
+impl Recorder {
+    /// Opens the recording at `path` for appending, creating it if needed
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Recorder {
+            file: Mutex::new(BufWriter::new(file)),
+        })
+    }
+
+    /// Writes one record. Each record is flushed right away so that the
+    /// traffic leading up to a crash is on disk.
+    pub fn record(&self, record: &Record) -> io::Result<()> {
+        let mut file = self.file.lock().unwrap();
+        serde_json::to_writer(&mut *file, record)?;
+        file.write_all(b"\n")?;
+        file.flush()
+    }
+}
+
+/// Reads every record of a recording, skipping blank lines
+pub fn read_recording<P: AsRef<Path>>(path: P) -> io::Result<Vec<Record>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut records = Vec::new();
+    for (number, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = serde_json::from_str(&line).map_err(|e| {
+            io::Error::new(
+                ErrorKind::InvalidData,
+                format!("line {}: {}", number + 1, e),
+            )
+        })?;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// A response that differs from the recorded one
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mismatch {
+    pub connection: ConnectionId,
+    /// Position of the message within its connection, from 0
+    pub index: usize,
+    pub request: Option<ClientMessage>,
+    pub expected: Option<ServerMessage>,
+    pub actual: Option<ServerMessage>,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "connection {}, message {}:", self.connection, self.index)?;
+        writeln!(f, "  request:  {}", json(&self.request))?;
+        writeln!(f, "  expected: {}", json(&self.expected))?;
+        write!(f, "  actual:   {}", json(&self.actual))
+    }
+}
+
+fn json<T: Serialize>(value: &T) -> String {
+    serde_json::to_string(value).unwrap_or_else(|e| format!("<{}>", e))
+}
+
+/// Outcome of a replay
+#[derive(Debug, Default)]
+pub struct ReplayReport {
+    /// Number of messages sent
+    pub replayed: usize,
+    pub mismatches: Vec<Mismatch>,
+}
+
+/// Re-sends `records` to the server at `addr` and compares the responses.
+///
+/// Each recorded connection is replayed over its own TCP connection, in the
+/// order the connections first appear. `timeout` bounds the wait for each
+/// recorded response. `framing` must match the server's.
+pub fn replay(
+    records: &[Record],
+    addr: &str,
+    framing: Framing,
+    timeout: Duration,
+) -> io::Result<ReplayReport> {
+    let mut connections: Vec<(ConnectionId, Vec<&Record>)> = Vec::new();
+    for record in records {
+        match connections
+            .iter_mut()
+            .find(|(id, _)| *id == record.connection)
+        {
+            Some((_, records)) => records.push(record),
+            None => connections.push((record.connection, vec![record])),
+        }
+    }
+
+    let mut report = ReplayReport::default();
+    for (connection, records) in connections {
+        let mut stream = TcpStream::connect(addr)?;
+        for (index, record) in records.into_iter().enumerate() {
+            stream.write_all(&framing.frame(&from_hex(&record.raw)?))?;
+            let wait = if record.response.is_some() {
+                timeout
+            } else {
+                NO_RESPONSE_WAIT
+            };
+            let actual = read_response(&mut stream, framing, wait)?;
+            report.replayed += 1;
+
+            if actual != record.response {
+                report.mismatches.push(Mismatch {
+                    connection,
+                    index,
+                    request: record.request.clone(),
+                    expected: record.response.clone(),
+                    actual,
+                });
+            }
+        }
+    }
+    Ok(report)
+}
+
+fn read_response(
+    stream: &mut TcpStream,
+    framing: Framing,
+    timeout: Duration,
+) -> io::Result<Option<ServerMessage>> {
+    stream.set_read_timeout(Some(timeout))?;
+    let mut buffer = vec![0u8; 65536];
+    let mut received = Vec::new();
+    loop {
+        let n = match stream.read(&mut buffer) {
+            Ok(0) => return Ok(None),
+            Ok(n) => n,
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                return Ok(None)
+            }
+            Err(e) => return Err(e),
+        };
+        let message = match framing {
+            Framing::Unframed => &buffer[..n],
+            Framing::LengthDelimited => {
+                received.extend_from_slice(&buffer[..n]);
+                match framing::next_frame(&received)? {
+                    Some(frame) => &received[frame],
+                    None => continue,
+                }
+            }
+        };
+        return ServerMessage::decode(message)
+            .map(Some)
+            .map_err(|e| io::Error::new(ErrorKind::InvalidData, e));
+    }
+}
+
+/// Replaces the token or HMAC of an `AuthRequest`; returns whether `request`
+/// is one
+pub(crate) fn redact_credentials(request: &mut ClientMessage) -> bool {
+    let Some(client_message::Message::AuthRequest(auth)) = &mut request.message else {
+        return false;
+    };
+    match &mut auth.credential {
+        Some(Credential::Token(token)) => *token = REDACTED.to_string(),
+        Some(Credential::Hmac(hmac)) => hmac.clear(),
+        None => {}
+    }
+    true
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Decodes `to_hex` output; an odd length fails on the last byte
+fn from_hex(hex: &str) -> io::Result<Vec<u8>> {
+    let invalid = || io::Error::new(ErrorKind::InvalidData, format!("invalid hex {:?}", hex));
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                .ok_or_else(invalid)
+        })
+        .collect()
+}
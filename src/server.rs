@@ -1,85 +1,127 @@
-use crate::message::{client_message, server_message, AddResponse};
-use log::{error, info, warn};
+use crate::admin;
+use crate::audit::{self, AuditEvent, AuditLog, Throttle};
+use crate::auth::{CredentialStore, Session};
+use crate::connection::{Connection, ConnectionRegistry, ConnectionSnapshot};
+use crate::filter::IpFilter;
+use crate::framing::{self, Framing};
+use crate::handler::{self, RequestContext};
+#[cfg(unix)]
+use crate::handoff;
+use crate::health::Health;
+#[cfg(unix)]
+use crate::listener::ListenerOptions;
+#[cfg(unix)]
+use crate::listener::UnixSocketConfig;
+use crate::listener::{ConnectionInfo, Listener, PeerAddr, Stream, StreamOptions};
+use crate::message::ErrorCode;
+use crate::message::ServingStatus;
+use crate::metrics::{Metrics, MetricsSnapshot};
+use crate::policy::Policy;
+#[cfg(feature = "record")]
+use crate::recorder::{self, Record, Recorder};
+#[cfg(unix)]
+use crate::systemd;
+#[cfg(feature = "tls")]
+use crate::tls::TlsConfig;
+use crate::validation::ValidationRules;
+use log::LevelFilter;
 use prost::Message;
+#[cfg(unix)]
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
 use std::{
     io::{self, ErrorKind, Read, Write},
-    net::{TcpListener, TcpStream},
     sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc, Mutex,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex, RwLock,
     },
     thread,
-    time::Duration,
+    time::{Duration, Instant},
+};
+use tracing::{debug, error, field, info, info_span, warn};
+#[cfg(feature = "record")]
+use {
+    crate::message::{ClientMessage, ServerMessage},
+    std::time::{SystemTime, UNIX_EPOCH},
 };
 
-#[derive(Clone, PartialEq, prost::Message)]
-pub struct ClientMessageWrapper {
-    #[prost(oneof = "client_message::Message", tags = "1, 2")]
-    pub message: Option<client_message::Message>,
-}
+pub use crate::connection::ConnectionId;
+pub use crate::handler::{ClientMessageWrapper, ServerMessageWrapper};
 
-#[derive(Clone, PartialEq, prost::Message)]
-pub struct ServerMessageWrapper {
-    #[prost(oneof = "server_message::Message", tags = "1, 2")]
-    pub message: Option<server_message::Message>,
-}
+/// How long an idle accept loop waits before re-checking `is_running`
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Refused connections audited one by one per second, the rest are counted
+const REFUSAL_AUDIT_LIMIT: u32 = 10;
 
 struct Client {
-    stream: TcpStream,
+    id: ConnectionId,
+    stream: Stream,
+    info: ConnectionInfo,
+    metrics: Arc<Metrics>,
+    health: Arc<Health>,
+    connection: Arc<Connection>,
+    /// Authentication state, when the server requires it
+    session: Option<Session>,
+    policy: Option<Arc<Policy>>,
+    audit: Option<Arc<AuditLog>>,
+    validation: Arc<ValidationRules>,
+    framing: Framing,
+    #[cfg(feature = "record")]
+    recorder: Option<Arc<Recorder>>,
 }
 
 impl Client {
-    pub fn new(stream: TcpStream) -> Self {
-        Client { stream }
+    pub fn new(
+        stream: Stream,
+        info: ConnectionInfo,
+        metrics: Arc<Metrics>,
+        health: Arc<Health>,
+        connection: Arc<Connection>,
+    ) -> Self {
+        Client {
+            id: connection.id(),
+            stream,
+            info,
+            metrics,
+            health,
+            connection,
+            session: None,
+            policy: None,
+            audit: None,
+            validation: Arc::default(),
+            framing: Framing::default(),
+            #[cfg(feature = "record")]
+            recorder: None,
+        }
     }
 
     pub fn handle(&mut self) -> io::Result<()> {
+        // The crate logs through `tracing`, so everything logged while serving
+        // this client, including by the handlers, is attributed to the
+        // connection. Messages still name the connection for plain `log` output.
+        let span = info_span!("connection", id = self.id, peer = %self.info.peer);
+        let _entered = span.enter();
+        // With framing, received bytes not yet making up a whole frame
+        let mut received = Vec::new();
         let mut buffer = [0; 65536]; // Increased buffer size for large payloads
 
         loop {
             match self.stream.read(&mut buffer) {
                 Ok(0) => {
-                    info!("Client disconnected.");
+                    info!("Connection {} ({}) disconnected.", self.id, self.info.peer);
                     break;
                 }
                 Ok(bytes_read) => {
-                    info!("Received {} bytes from client.", bytes_read); // Log message size
-                    match ClientMessageWrapper::decode(&buffer[..bytes_read]) {
-                        Ok(ClientMessageWrapper {
-                            message: Some(client_message::Message::EchoMessage(echo_message)),
-                        }) => {
-                            info!("Received EchoMessage: {}", echo_message.content);
-
-                            let response = ServerMessageWrapper {
-                                message: Some(server_message::Message::EchoMessage(echo_message)),
-                            };
-                            let payload = response.encode_to_vec();
-                            self.stream.write_all(&payload)?;
-                        }
-                        Ok(ClientMessageWrapper {
-                            message: Some(client_message::Message::AddRequest(add_request)),
-                        }) => {
-                            info!(
-                                "Received AddRequest: a = {}, b = {}",
-                                add_request.a, add_request.b
-                            );
-
-                            let result = add_request.a + add_request.b;
-                            let response = ServerMessageWrapper {
-                                message: Some(server_message::Message::AddResponse(AddResponse {
-                                    result,
-                                })),
-                            };
-                            let payload = response.encode_to_vec();
-                            self.stream.write_all(&payload)?;
-
-                            info!("Sent AddResponse: result = {}", result);
-                        }
-                        Ok(ClientMessageWrapper { message: None }) => {
-                            warn!("Received message with None type.");
-                        }
-                        Err(e) => {
-                            error!("Failed to decode message: {}", e);
+                    self.metrics.bytes_received(bytes_read);
+                    self.connection.stats().bytes_received(bytes_read);
+                    match self.framing {
+                        Framing::Unframed => self.process(&buffer[..bytes_read])?,
+                        Framing::LengthDelimited => {
+                            received.extend_from_slice(&buffer[..bytes_read]);
+                            self.process_frames(&mut received)?;
                         }
                     }
                 }
@@ -87,86 +129,711 @@ impl Client {
                     thread::sleep(Duration::from_millis(100));
                 }
                 Err(e) => {
-                    error!("Error reading from client: {}", e);
+                    error!("Error reading from connection {}: {}", self.id, e);
                     break;
                 }
             }
         }
         Ok(())
     }
+
+    /// Processes every complete frame at the start of `received` and removes
+    /// them. A malformed length prefix ends the connection, as the stream
+    /// cannot be resynchronized.
+    fn process_frames(&mut self, received: &mut Vec<u8>) -> io::Result<()> {
+        let mut consumed = 0;
+        while let Some(frame) = framing::next_frame(&received[consumed..])? {
+            let message = consumed + frame.start..consumed + frame.end;
+            consumed = message.end;
+            self.process(&received[message])?;
+        }
+        received.drain(..consumed);
+        Ok(())
+    }
+
+    /// Handles one received message and sends the response
+    fn process(&mut self, data: &[u8]) -> io::Result<()> {
+        let span = info_span!(
+            "request",
+            size = data.len(),
+            message_type = field::Empty,
+            request_id = field::Empty,
+            latency_us = field::Empty,
+            outcome = field::Empty,
+        );
+        let _entered = span.enter();
+        debug!("Received {} bytes on connection {}.", data.len(), self.id);
+        #[cfg(feature = "record")]
+        let received_at = SystemTime::now();
+
+        let request = match ClientMessageWrapper::decode(data) {
+            Ok(request) => request,
+            Err(e) => {
+                span.record("outcome", "decode_error");
+                error!("Failed to decode message on connection {}: {}", self.id, e);
+                self.metrics.decode_error();
+                self.connection.stats().decode_error();
+                #[cfg(feature = "record")]
+                self.record(data, received_at, Duration::ZERO, None);
+                return Ok(());
+            }
+        };
+        let message_type = handler::request_type(&request);
+        span.record("message_type", message_type);
+        span.record("request_id", request.request_id);
+
+        let start = Instant::now();
+        let auth_response = self
+            .session
+            .as_mut()
+            .and_then(|session| session.check(&request, &self.connection))
+            .or_else(|| self.authorize(&request, message_type));
+        let response = auth_response.or_else(|| {
+            handler::handle_request(
+                request,
+                &RequestContext {
+                    health: &self.health,
+                    metrics: Some(&self.metrics),
+                    connection: Some(&self.connection),
+                    validation: Some(&self.validation),
+                },
+            )
+        });
+        let latency = start.elapsed();
+        span.record("latency_us", latency.as_micros() as u64);
+        self.metrics.request_handled(message_type, latency);
+        self.connection.stats().request();
+        #[cfg(feature = "record")]
+        self.record(data, received_at, latency, response.as_ref());
+
+        let Some(response) = response else {
+            span.record("outcome", "no_response");
+            return Ok(());
+        };
+        let payload = self.framing.encode(&response);
+        if let Err(e) = self.stream.write_all(&payload) {
+            span.record("outcome", "write_error");
+            return Err(e);
+        }
+        self.metrics.bytes_sent(payload.len());
+        self.connection.stats().bytes_sent(payload.len());
+        span.record("outcome", "ok");
+        Ok(())
+    }
+
+    /// Rejects requests the connection's identity may not make, if the server
+    /// has a policy
+    fn authorize(
+        &self,
+        request: &ClientMessageWrapper,
+        message_type: &str,
+    ) -> Option<ServerMessageWrapper> {
+        let policy = self.policy.as_ref()?;
+        let identity = self.connection.identity();
+        if policy.allows(identity.as_deref(), message_type) {
+            return None;
+        }
+        let identity = identity.as_deref().unwrap_or("unauthenticated");
+        warn!(
+            "Connection {} ({}) may not send {}",
+            self.id, identity, message_type
+        );
+        audit::record(
+            self.audit.as_deref(),
+            Some(self.id),
+            AuditEvent::PermissionDenied {
+                identity: identity.to_string(),
+                message_type: message_type.to_string(),
+            },
+        );
+        Some(handler::error_response(
+            request.request_id,
+            ErrorCode::PermissionDenied,
+            format!("{} may not send {}", identity, message_type),
+        ))
+    }
+
+    /// Appends the message and its response to the recording, if any.
+    /// Recording failures are logged but do not affect the connection.
+    #[cfg(feature = "record")]
+    fn record(
+        &self,
+        data: &[u8],
+        received_at: SystemTime,
+        latency: Duration,
+        response: Option<&ServerMessageWrapper>,
+    ) {
+        let Some(recorder) = &self.recorder else {
+            return;
+        };
+        // Credentials never reach the recording, not even as raw bytes
+        let mut request = ClientMessage::decode(data).ok();
+        let redacted = request.as_mut().is_some_and(recorder::redact_credentials);
+        let raw = match &request {
+            Some(request) if redacted => recorder::to_hex(&request.encode_to_vec()),
+            _ => recorder::to_hex(data),
+        };
+        let record = Record {
+            timestamp_ms: received_at
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+            connection: self.id,
+            peer: self.info.peer.to_string(),
+            raw,
+            request,
+            response: response.cloned().map(ServerMessage::from),
+            latency_us: latency.as_micros() as u64,
+        };
+        if let Err(e) = recorder.record(&record) {
+            warn!("Failed to record message on connection {}: {}", self.id, e);
+        }
+    }
+}
+
+/// Unregisters a connection when its thread ends, even by panicking, so that
+/// the server does not wait for it forever
+struct ConnectionGuard {
+    id: ConnectionId,
+    connections: Arc<ConnectionRegistry>,
+    metrics: Arc<Metrics>,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.connections.remove(self.id);
+        self.metrics.connection_closed();
+    }
 }
 
 pub struct Server {
-    listener: TcpListener,
+    listeners: Vec<Listener>,
     is_running: Arc<AtomicBool>,
-    address: String, // Store the address the server is bound to
+    addresses: Vec<String>, // Store the addresses the server is bound to
+    stream_options: StreamOptions,
     clients: Arc<Mutex<Vec<thread::JoinHandle<()>>>>,
+    metrics: Arc<Metrics>,
+    /// Lifecycle state, as reported to health checks
+    health: Arc<Health>,
+    /// Credentials connections must authenticate with, if any
+    credentials: Option<Arc<CredentialStore>>,
+    /// Message types each identity may send, if restricted
+    policy: Option<Arc<Policy>>,
+    /// Peer addresses connections are accepted from, replaceable at runtime
+    ip_filter: RwLock<Option<Arc<IpFilter>>>,
+    /// Where security-relevant events are recorded, if anywhere
+    audit: Option<Arc<AuditLog>>,
+    /// Limits the refused connections audited during a flood
+    refusals: Throttle,
+    /// Rules requests must satisfy before they are handled
+    validation: Arc<ValidationRules>,
+    /// How messages are delimited on connections
+    framing: Framing,
+    next_connection_id: AtomicU64,
+    connections: Arc<ConnectionRegistry>,
+    /// Listener for the admin control channel
+    admin: Option<Listener>,
+    /// Applies log levels set on the admin channel to a tracing subscriber
+    log_level: Option<Box<dyn Fn(LevelFilter) -> io::Result<()> + Send + Sync>>,
+    /// Unix socket offering the listeners to a replacement process
+    #[cfg(unix)]
+    handoff: Option<(Listener, PathBuf)>,
+    /// Set once the listeners belong to a replacement process
+    #[cfg(unix)]
+    handed_off: AtomicBool,
+    /// Where received messages and their responses are recorded
+    #[cfg(feature = "record")]
+    recorder: Option<Arc<Recorder>>,
 }
 
 impl Server {
     /// Creates a new server instance
     pub fn new(addr: &str) -> io::Result<Self> {
-        let listener = TcpListener::bind(addr)?;
-        let local_addr = listener.local_addr()?; // Retrieve the actual address the server is bound to
-        listener.set_nonblocking(true)?;
+        Self::with_listener(Listener::bind_tcp(addr)?)
+    }
+
+    /// Creates a new server with `acceptors` listeners sharing `addr` through
+    /// SO_REUSEPORT, each with its own accept thread. The kernel load-balances
+    /// incoming connections between them, which raises the accept rate during
+    /// reconnect storms.
+    #[cfg(unix)]
+    pub fn new_reuseport(addr: &str, acceptors: usize) -> io::Result<Self> {
+        Self::from_listeners(Listener::bind_reuseport(
+            addr,
+            acceptors,
+            &ListenerOptions::default(),
+        )?)
+    }
+
+    /// Creates a new server that serves TLS on `addr`
+    #[cfg(feature = "tls")]
+    pub fn new_tls(addr: &str, config: &TlsConfig) -> io::Result<Self> {
+        Self::with_listener(Listener::bind_tls(addr, config.server_config()?)?)
+    }
+
+    /// Creates a new server that accepts WebSocket connections on `addr`
+    #[cfg(feature = "websocket")]
+    pub fn new_websocket(addr: &str) -> io::Result<Self> {
+        Self::with_listener(Listener::bind_websocket(addr)?)
+    }
+
+    /// Creates a new server listening on a Unix domain socket at `path`
+    #[cfg(unix)]
+    pub fn bind_unix<P: AsRef<Path>>(path: P, config: &UnixSocketConfig) -> io::Result<Self> {
+        Self::with_listener(Listener::bind_unix(path, config)?)
+    }
+
+    /// Creates a server accepting connections from every given listener.
+    ///
+    /// Listeners can mix transports (e.g. IPv4, IPv6 and Unix sockets); all of
+    /// them dispatch into the same handlers.
+    pub fn from_listeners(listeners: Vec<Listener>) -> io::Result<Self> {
+        if listeners.is_empty() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "a server needs at least one listener",
+            ));
+        }
+
+        let mut addresses = Vec::with_capacity(listeners.len());
+        for listener in &listeners {
+            // Retrieve the actual address each listener is bound to
+            addresses.push(listener.local_addr()?);
+            listener.set_nonblocking(true)?;
+        }
         Ok(Server {
-            listener,
+            listeners,
             is_running: Arc::new(AtomicBool::new(false)),
-            address: local_addr.to_string(),
+            addresses,
+            stream_options: StreamOptions::default(),
+            framing: Framing::default(),
             clients: Arc::new(Mutex::new(Vec::new())),
+            metrics: Arc::new(Metrics::new()),
+            health: Arc::new(Health::new(ServingStatus::Starting)),
+            credentials: None,
+            policy: None,
+            ip_filter: RwLock::new(None),
+            refusals: Throttle::new(
+                "connection_refused",
+                REFUSAL_AUDIT_LIMIT,
+                Duration::from_secs(1),
+            ),
+            log_level: None,
+            audit: None,
+            validation: Arc::default(),
+            next_connection_id: AtomicU64::new(1),
+            connections: Arc::new(ConnectionRegistry::new()),
+            admin: None,
+            #[cfg(unix)]
+            handoff: None,
+            #[cfg(unix)]
+            handed_off: AtomicBool::new(false),
+            #[cfg(feature = "record")]
+            recorder: None,
         })
     }
-    /// Returns the server's address
+
+    /// Creates a server from the sockets passed by systemd socket activation.
+    ///
+    /// Fails with `NotFound` when the process was not socket activated or the
+    /// sockets were already adopted.
+    #[cfg(unix)]
+    pub fn from_systemd() -> io::Result<Self> {
+        let listeners = systemd::listeners()?;
+        if listeners.is_empty() {
+            return Err(io::Error::new(
+                ErrorKind::NotFound,
+                "no sockets were passed by systemd, or they were already adopted",
+            ));
+        }
+        Self::from_listeners(listeners)
+    }
+
+    /// Creates a server from the listeners of a running server, which offers
+    /// them on the handoff socket at `path` (see `with_handoff`)
+    #[cfg(unix)]
+    pub fn from_handoff<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::from_listeners(handoff::receive_listeners(path)?)
+    }
+
+    /// Offers the listeners to a replacement process on a Unix socket at
+    /// `path`, for zero-downtime upgrades.
+    ///
+    /// Once a new process has taken them over with `from_handoff`, this server
+    /// stops accepting and `run()` returns after its existing connections
+    /// have closed. TLS listeners cannot be handed over.
+    #[cfg(unix)]
+    pub fn with_handoff<P: AsRef<Path>>(mut self, path: P) -> io::Result<Self> {
+        handoff::check_listeners(&self.listeners)?;
+        let config = UnixSocketConfig {
+            // The listeners are as good as the server itself
+            mode: Some(0o600),
+            ..Default::default()
+        };
+        let listener = Listener::bind_unix(&path, &config)?;
+        listener.set_nonblocking(true)?;
+        self.handoff = Some((listener, path.as_ref().to_path_buf()));
+        Ok(self)
+    }
+
+    /// Serves the admin control channel (see the `admin` module) on `listener`.
+    ///
+    /// Admin commands are not authenticated; use a listener only operators
+    /// can reach, such as a Unix socket with a restrictive mode.
+    pub fn with_admin(mut self, listener: Listener) -> io::Result<Self> {
+        listener.set_nonblocking(true)?;
+        self.admin = Some(listener);
+        Ok(self)
+    }
+
+    /// Records every received message with its response (see the `recorder`
+    /// module), for replay with the `replay` tool
+    #[cfg(feature = "record")]
+    pub fn with_recorder(mut self, recorder: Recorder) -> Self {
+        self.recorder = Some(Arc::new(recorder));
+        self
+    }
+
+    /// Requires connections to authenticate against `store` before their
+    /// requests are handled (see the `auth` module)
+    pub fn with_credentials(mut self, store: CredentialStore) -> Self {
+        self.credentials = Some(Arc::new(store));
+        self
+    }
+
+    /// Restricts the message types each identity may send (see the `policy`
+    /// module). Requests that are not allowed get a `PermissionDenied` error.
+    pub fn with_policy(mut self, policy: Policy) -> Self {
+        self.policy = Some(Arc::new(policy));
+        self
+    }
+
+    /// Refuses connections from peers outside `filter`, right after accepting
+    /// them (see the `filter` module)
+    pub fn with_ip_filter(self, filter: IpFilter) -> Self {
+        self.set_ip_filter(Some(filter));
+        self
+    }
+
+    /// Records authentication attempts, authorization denials, admin
+    /// commands, forced disconnects and refused connections in `audit`
+    pub fn with_audit_log(mut self, audit: AuditLog) -> Self {
+        self.audit = Some(Arc::new(audit));
+        self
+    }
+
+    /// Checks requests against `rules` before they are handled; violations
+    /// are answered with an `InvalidArgument` error naming the field
+    pub fn with_validation(mut self, rules: ValidationRules) -> Self {
+        self.validation = Arc::new(rules);
+        self
+    }
+
+    /// Calls `apply` with each log level set through `set_log_level`, e.g. the
+    /// admin channel. Once a tracing subscriber is installed the `log` level
+    /// no longer filters anything, so `apply` should pass the level on to the
+    /// subscriber, typically through a `tracing_subscriber::reload` handle.
+    pub fn on_log_level<F>(mut self, apply: F) -> Self
+    where
+        F: Fn(LevelFilter) -> io::Result<()> + Send + Sync + 'static,
+    {
+        self.log_level = Some(Box::new(apply));
+        self
+    }
+
+    /// Sets how messages are delimited on every connection. Clients with
+    /// several requests in flight, like `AsyncClient`, need
+    /// `Framing::LengthDelimited`.
+    pub fn with_framing(mut self, framing: Framing) -> Self {
+        self.framing = framing;
+        self
+    }
+
+    /// Sets the socket options applied to every accepted connection
+    pub fn with_stream_options(mut self, options: StreamOptions) -> Self {
+        self.stream_options = options;
+        self
+    }
+
+    fn with_listener(listener: Listener) -> io::Result<Self> {
+        Self::from_listeners(vec![listener])
+    }
+
+    /// Returns the server's address, the first one if it has several
     pub fn address(&self) -> &str {
-        &self.address
+        &self.addresses[0]
+    }
+
+    /// Returns every address the server is listening on
+    pub fn addresses(&self) -> &[String] {
+        &self.addresses
+    }
+
+    /// Returns a snapshot of the server's metrics
+    pub fn metrics(&self) -> MetricsSnapshot {
+        self.metrics.snapshot()
+    }
+
+    /// Returns the live metrics, e.g. to serve them with a `MetricsExporter`
+    pub fn metrics_registry(&self) -> Arc<Metrics> {
+        self.metrics.clone()
+    }
+
+    /// Returns the lifecycle state
+    pub fn status(&self) -> ServingStatus {
+        self.health.status()
+    }
+
+    /// Returns the shared health state, e.g. for a `HealthEndpoint`
+    pub fn health(&self) -> Arc<Health> {
+        self.health.clone()
+    }
+
+    /// Returns the IP filter in effect, if any
+    pub fn ip_filter(&self) -> Option<Arc<IpFilter>> {
+        self.ip_filter.read().unwrap().clone()
+    }
+
+    /// Replaces the IP filter. It applies to connections accepted from now
+    /// on; open connections are not affected.
+    pub fn set_ip_filter(&self, filter: Option<IpFilter>) {
+        *self.ip_filter.write().unwrap() = filter.map(Arc::new);
+    }
+
+    /// Reloads the IP filter from the file it was loaded from. On error the
+    /// current filter stays in effect.
+    pub fn reload_ip_filter(&self) -> io::Result<()> {
+        let source = self
+            .ip_filter()
+            .and_then(|filter| filter.source().map(|path| path.to_path_buf()))
+            .ok_or_else(|| {
+                io::Error::new(
+                    ErrorKind::NotFound,
+                    "the IP filter was not loaded from a file",
+                )
+            })?;
+        let filter = IpFilter::from_file(&source)?;
+        info!("Reloaded IP filter from {}", source.display());
+        self.set_ip_filter(Some(filter));
+        Ok(())
+    }
+
+    /// Sets the maximum level of the `log` backend, and of the tracing
+    /// subscriber if the application passed an `on_log_level` callback
+    pub fn set_log_level(&self, level: LevelFilter) -> io::Result<()> {
+        if let Some(apply) = &self.log_level {
+            apply(level)?;
+        }
+        log::set_max_level(level);
+        info!("Log level set to {}", level);
+        Ok(())
+    }
+
+    /// Whether the IP filter lets `info`'s peer connect. Unix peers have no
+    /// IP address and are always let in.
+    fn accepts(&self, info: &ConnectionInfo) -> bool {
+        match (&info.peer, self.ip_filter.read().unwrap().as_ref()) {
+            (PeerAddr::Tcp(addr), Some(filter)) => filter.allows(addr.ip()),
+            _ => true,
+        }
+    }
+
+    /// Returns the open connections, ordered by ID
+    pub fn connections(&self) -> Vec<ConnectionSnapshot> {
+        self.connections.list()
+    }
+
+    /// Returns an open connection by ID
+    pub fn connection(&self, id: ConnectionId) -> Option<ConnectionSnapshot> {
+        self.connections.get(id)
+    }
+
+    /// Force-disconnects a connection. Fails with `NotFound` if it is not open.
+    pub fn disconnect(&self, id: ConnectionId) -> io::Result<()> {
+        info!("Disconnecting connection {}", id);
+        let peer = self.connections.get(id).map(|c| c.info.peer.to_string());
+        self.connections.disconnect(id)?;
+        if let Some(peer) = peer {
+            self.audit(Some(id), AuditEvent::ForcedDisconnect { peer });
+        }
+        Ok(())
+    }
+
+    /// Records `event` in the audit log, if there is one
+    pub(crate) fn audit(&self, connection: Option<ConnectionId>, event: AuditEvent) {
+        audit::record(self.audit.as_deref(), connection, event);
+    }
+
+    /// Stops accepting new connections. Open connections are served until
+    /// they close, after which `run()` returns.
+    pub fn drain(&self) {
+        if self.is_running.swap(false, Ordering::SeqCst) {
+            self.health.set(ServingStatus::Draining);
+            info!(
+                "Draining server, {} connection(s) open.",
+                self.connections.len()
+            );
+        }
+    }
+
+    /// Whether the server is accepting or still serving connections
+    pub(crate) fn is_up(&self) -> bool {
+        self.is_running.load(Ordering::SeqCst) || !self.connections.is_empty()
     }
 
     /// Runs the server, accepting connections and handling them concurrently
     pub fn run(&self) -> io::Result<()> {
         self.is_running.store(true, Ordering::SeqCst); // Set the server as running
-        info!("Server is running on {}", self.listener.local_addr()?);
+        self.health.set(ServingStatus::Serving);
+        info!("Server is running on {}", self.addresses.join(", "));
+
+        // Each listener gets its own accept thread, so several sockets sharing
+        // a port through SO_REUSEPORT are drained in parallel
+        thread::scope(|scope| {
+            for listener in &self.listeners {
+                scope.spawn(move || self.accept_loop(listener));
+            }
+            if let Some(listener) = &self.admin {
+                scope.spawn(move || admin::serve(self, listener));
+            }
+            #[cfg(unix)]
+            if let Some((listener, path)) = &self.handoff {
+                scope.spawn(move || self.handoff_loop(listener, path));
+            }
+        });
 
+        // Accepting has stopped, only open connections are being served now
+        self.refusals.flush(self.audit.as_deref(), true);
+        self.health.set(ServingStatus::Draining);
+        info!("Server stopping. Waiting for all client threads to finish...");
+
+        // Wait for all client threads to finish
+        let mut clients = self.clients.lock().unwrap();
+        while let Some(handle) = clients.pop() {
+            handle
+                .join()
+                .unwrap_or_else(|_| warn!("A client thread failed to join."));
+        }
+        info!("All client threads finished.");
+        self.health.set(ServingStatus::Stopped);
+        Ok(())
+    }
+
+    fn accept_loop(&self, listener: &Listener) {
         while self.is_running.load(Ordering::SeqCst) {
-            match self.listener.accept() {
-                Ok((stream, addr)) => {
-                    info!("New client connected: {}", addr);
+            match listener.accept_with(&self.stream_options) {
+                Ok((stream, conn_info)) => {
+                    if !self.accepts(&conn_info) {
+                        info!("Refused connection from {} (IP filter)", conn_info);
+                        self.metrics.connection_rejected();
+                        self.refusals.record(
+                            self.audit.as_deref(),
+                            None,
+                            AuditEvent::ConnectionRefused {
+                                peer: conn_info.peer.to_string(),
+                            },
+                        );
+                        continue;
+                    }
+                    let id = self.next_connection_id.fetch_add(1, Ordering::Relaxed);
+                    info!("New client connected: {} (connection {})", conn_info, id);
+                    self.metrics.connection_accepted();
 
-                    let mut client = Client::new(stream);
+                    let closer = stream
+                        .closer()
+                        .map_err(|e| warn!("Connection {} cannot be closed: {}", id, e))
+                        .ok();
+                    let connection = self.connections.register(id, conn_info.clone(), closer);
+                    let connections = self.connections.clone();
+                    let metrics = self.metrics.clone();
+                    let mut client = Client::new(
+                        stream,
+                        conn_info,
+                        metrics.clone(),
+                        self.health.clone(),
+                        connection,
+                    );
+                    client.session = self
+                        .credentials
+                        .clone()
+                        .map(|store| Session::new(store, self.audit.clone()));
+                    client.policy = self.policy.clone();
+                    client.audit = self.audit.clone();
+                    client.validation = self.validation.clone();
+                    client.framing = self.framing;
+                    #[cfg(feature = "record")]
+                    {
+                        client.recorder = self.recorder.clone();
+                    }
                     let handle = thread::spawn(move || {
+                        let _closed = ConnectionGuard {
+                            id,
+                            connections,
+                            metrics,
+                        };
                         client
                             .handle()
-                            .unwrap_or_else(|e| error!("Client error: {}", e));
+                            .unwrap_or_else(|e| error!("Client {} error: {}", id, e));
                     });
 
                     self.clients.lock().unwrap().push(handle);
                 }
                 Err(ref e) if e.kind() == ErrorKind::WouldBlock => {
-                    // No incoming connections, sleep briefly to reduce CPU usage
-                    thread::sleep(Duration::from_millis(100));
+                    self.refusals.flush(self.audit.as_deref(), false);
+                    // No incoming connections, wait for one without spinning.
+                    // The timeout bounds how long `stop()` takes to be noticed.
+                    if let Err(e) = listener.wait_for_connection(ACCEPT_POLL_INTERVAL) {
+                        error!("Error waiting for connections: {}", e);
+                        thread::sleep(ACCEPT_POLL_INTERVAL);
+                    }
                 }
                 Err(e) => {
                     error!("Error accepting connection: {}", e);
                 }
             }
         }
+    }
 
-        info!("Server stopping. Waiting for all client threads to finish...");
-
-        // Wait for all client threads to finish
-        let mut clients = self.clients.lock().unwrap();
-        while let Some(handle) = clients.pop() {
-            handle
-                .join()
-                .unwrap_or_else(|_| warn!("A client thread failed to join."));
+    #[cfg(unix)]
+    fn handoff_loop(&self, listener: &Listener, path: &Path) {
+        while self.is_running.load(Ordering::SeqCst) {
+            match listener.accept() {
+                Ok((Stream::Unix(mut stream), _)) => {
+                    info!("Handing listeners over to a new process...");
+                    if let Err(e) = handoff::send_listeners(&mut stream, &self.listeners) {
+                        warn!("Listener handoff failed, still serving: {}", e);
+                        continue;
+                    }
+                    // Free the path before the new process is told we are
+                    // done, so that it can offer its own handoff socket there
+                    if let Err(e) = fs::remove_file(path) {
+                        warn!("Failed to remove {}: {}", path.display(), e);
+                    }
+                    self.handed_off.store(true, Ordering::SeqCst);
+                    self.is_running.store(false, Ordering::SeqCst);
+                    self.health.set(ServingStatus::Draining);
+                    info!("Listeners handed over. Draining existing connections...");
+                    return;
+                }
+                Ok(_) => unreachable!("Unix listeners accept Unix streams"),
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => {
+                    if let Err(e) = listener.wait_for_connection(ACCEPT_POLL_INTERVAL) {
+                        error!("Error waiting for handoff connections: {}", e);
+                        thread::sleep(ACCEPT_POLL_INTERVAL);
+                    }
+                }
+                Err(e) => {
+                    error!("Error accepting handoff connection: {}", e);
+                }
+            }
         }
-        info!("All client threads finished.");
-        Ok(())
     }
 
     /// Stops the server by setting the `is_running` flag to `false`
     pub fn stop(&self) {
         if self.is_running.load(Ordering::SeqCst) {
             self.is_running.store(false, Ordering::SeqCst);
+            self.health.set(ServingStatus::Draining);
             info!("Shutdown signal sent. Waiting for server to stop...");
 
             // Wait up to 5 seconds for the server to stop
@@ -185,3 +852,18 @@ impl Server {
         }
     }
 }
+
+#[cfg(unix)]
+impl Drop for Server {
+    fn drop(&mut self) {
+        // Socket files of handed over listeners belong to the new process now
+        if *self.handed_off.get_mut() {
+            for listener in &mut self.listeners {
+                listener.keep_socket_file();
+            }
+            if let Some((listener, _)) = &mut self.handoff {
+                listener.keep_socket_file();
+            }
+        }
+    }
+}
@@ -0,0 +1,77 @@
+//! systemd socket activation.
+//!
+//! systemd binds the sockets listed in the service's `.socket` unit and passes
+//! them to the process as file descriptors 3, 4, ..., announced through the
+//! `LISTEN_PID` and `LISTEN_FDS` environment variables. The sockets outlive
+//! the process, so the service can be started on demand and restarted without
+//! refusing connections in between.
+
+use crate::listener::Listener;
+use std::{
+    env, io,
+    os::unix::io::{FromRawFd, OwnedFd, RawFd},
+    process,
+    sync::atomic::{AtomicBool, Ordering},
+};
+use tracing::info;
+
+/// First file descriptor passed by systemd (`SD_LISTEN_FDS_START`)
+pub const LISTEN_FDS_START: RawFd = 3;
+
+/// Most descriptors accepted from `LISTEN_FDS`, far more than a unit will list
+const MAX_LISTEN_FDS: RawFd = 1024;
+
+/// Set once the descriptors have been adopted
+static ADOPTED: AtomicBool = AtomicBool::new(false);
+
+/// Takes ownership of the file descriptors passed by systemd.
+///
+/// Returns an empty list when the process was not socket activated, or when
+/// the descriptors were already adopted by an earlier call. The environment
+/// is left untouched: child processes ignore the variables because
+/// `LISTEN_PID` names this process, and the descriptors are close-on-exec.
+pub fn listen_fds() -> io::Result<Vec<OwnedFd>> {
+    let (Ok(pid), Ok(fds)) = (env::var("LISTEN_PID"), env::var("LISTEN_FDS")) else {
+        return Ok(Vec::new());
+    };
+    // The variables may have been inherited from a parent that was activated
+    if pid.parse::<u32>().map_err(invalid_env)? != process::id() {
+        return Ok(Vec::new());
+    }
+    let count: RawFd = fds.parse().map_err(invalid_env)?;
+    if !(0..=MAX_LISTEN_FDS).contains(&count) {
+        return Err(invalid_env(format!(
+            "LISTEN_FDS={} is not between 0 and {}",
+            count, MAX_LISTEN_FDS
+        )));
+    }
+    if ADOPTED.swap(true, Ordering::SeqCst) {
+        return Ok(Vec::new());
+    }
+
+    let mut owned = Vec::with_capacity(count as usize);
+    for fd in LISTEN_FDS_START..LISTEN_FDS_START + count {
+        // Also checks that the descriptor is open, fcntl fails with EBADF otherwise
+        // SAFETY: fcntl only touches the descriptor flags
+        if unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: the descriptor is open and systemd handed it to this process;
+        // the `ADOPTED` flag guarantees it is only adopted once
+        owned.push(unsafe { OwnedFd::from_raw_fd(fd) });
+    }
+    info!("Adopted {} socket(s) from systemd", owned.len());
+    Ok(owned)
+}
+
+/// Adopts every listening socket passed by systemd
+pub fn listeners() -> io::Result<Vec<Listener>> {
+    listen_fds()?.into_iter().map(Listener::from_fd).collect()
+}
+
+fn invalid_env<E: std::fmt::Display>(e: E) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid socket activation environment: {}", e),
+    )
+}
@@ -0,0 +1,105 @@
+use rustls::{
+    pki_types::{CertificateDer, PrivateKeyDer},
+    server::WebPkiClientVerifier,
+    ClientConfig, RootCertStore, ServerConfig,
+};
+use std::{
+    fs::File,
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// Certificate and key material for the server side of a TLS connection
+#[derive(Debug, Clone)]
+pub struct TlsConfig {
+    /// PEM file with the server certificate chain, leaf first
+    pub cert_chain: PathBuf,
+    /// PEM file with the server private key
+    pub private_key: PathBuf,
+    /// PEM file with the CA certificates trusted to sign client certificates.
+    /// When set, clients must present a valid certificate (mutual TLS).
+    pub client_ca: Option<PathBuf>,
+}
+
+impl TlsConfig {
+    /// Loads the certificates and key and builds a rustls server configuration
+    pub fn server_config(&self) -> io::Result<Arc<ServerConfig>> {
+        let certs = load_certs(&self.cert_chain)?;
+        let key = load_private_key(&self.private_key)?;
+
+        let builder = match &self.client_ca {
+            Some(ca) => {
+                let roots = Arc::new(load_root_store(ca)?);
+                let verifier = WebPkiClientVerifier::builder(roots)
+                    .build()
+                    .map_err(invalid_data)?;
+                ServerConfig::builder().with_client_cert_verifier(verifier)
+            }
+            None => ServerConfig::builder().with_no_client_auth(),
+        };
+
+        let config = builder.with_single_cert(certs, key).map_err(invalid_data)?;
+        Ok(Arc::new(config))
+    }
+}
+
+/// Trust anchors and optional client identity for connecting to a TLS server
+#[derive(Debug, Clone)]
+pub struct TlsClientConfig {
+    /// PEM file with the CA certificates trusted to sign the server certificate
+    pub ca: PathBuf,
+    /// PEM files with the client certificate chain and key, for mutual TLS
+    pub identity: Option<(PathBuf, PathBuf)>,
+}
+
+impl TlsClientConfig {
+    /// Loads the certificates and key and builds a rustls client configuration
+    pub fn client_config(&self) -> io::Result<Arc<ClientConfig>> {
+        let builder = ClientConfig::builder().with_root_certificates(load_root_store(&self.ca)?);
+
+        let config = match &self.identity {
+            Some((cert_chain, private_key)) => builder
+                .with_client_auth_cert(load_certs(cert_chain)?, load_private_key(private_key)?)
+                .map_err(invalid_data)?,
+            None => builder.with_no_client_auth(),
+        };
+        Ok(Arc::new(config))
+    }
+}
+
+/// Reads every certificate from a PEM file
+pub fn load_certs(path: &Path) -> io::Result<Vec<CertificateDer<'static>>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let certs = rustls_pemfile::certs(&mut reader).collect::<io::Result<Vec<_>>>()?;
+    if certs.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("no certificates found in {}", path.display()),
+        ));
+    }
+    Ok(certs)
+}
+
+/// Reads the first private key (PKCS#1, PKCS#8 or SEC1) from a PEM file
+pub fn load_private_key(path: &Path) -> io::Result<PrivateKeyDer<'static>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    rustls_pemfile::private_key(&mut reader)?.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("no private key found in {}", path.display()),
+        )
+    })
+}
+
+fn load_root_store(path: &Path) -> io::Result<RootCertStore> {
+    let mut roots = RootCertStore::empty();
+    for cert in load_certs(path)? {
+        roots.add(cert).map_err(invalid_data)?;
+    }
+    Ok(roots)
+}
+
+fn invalid_data<E: std::error::Error + Send + Sync + 'static>(e: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
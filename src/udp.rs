@@ -0,0 +1,210 @@
+use crate::handler::{self, ClientMessageWrapper, RequestContext};
+use crate::health::Health;
+use crate::message::ServingStatus;
+use crate::validation::ValidationRules;
+use prost::Message;
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{self, ErrorKind},
+    net::{SocketAddr, UdpSocket},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tracing::{error, info, warn};
+
+/// Largest payload a single UDP datagram can carry over IPv4
+pub const MAX_UDP_PAYLOAD: usize = 65_507;
+
+/// Options for the UDP transport
+#[derive(Debug, Clone)]
+pub struct UdpConfig {
+    /// Datagrams (requests and responses) larger than this are dropped
+    pub max_datagram_size: usize,
+    /// Number of recent responses remembered for duplicate suppression
+    pub dedup_capacity: usize,
+    /// Rules requests must satisfy before they are handled
+    pub validation: ValidationRules,
+}
+
+impl Default for UdpConfig {
+    fn default() -> Self {
+        UdpConfig {
+            max_datagram_size: MAX_UDP_PAYLOAD,
+            dedup_capacity: 1024,
+            validation: ValidationRules::default(),
+        }
+    }
+}
+
+/// Remembers the responses sent for recent `(peer, request_id)` pairs so a
+/// retransmitted request gets the same answer without running the handler again
+struct ResponseCache {
+    capacity: usize,
+    order: VecDeque<(SocketAddr, u64)>,
+    responses: HashMap<(SocketAddr, u64), Vec<u8>>,
+}
+
+impl ResponseCache {
+    fn new(capacity: usize) -> Self {
+        ResponseCache {
+            capacity,
+            order: VecDeque::with_capacity(capacity),
+            responses: HashMap::with_capacity(capacity),
+        }
+    }
+
+    fn get(&self, key: &(SocketAddr, u64)) -> Option<&[u8]> {
+        self.responses.get(key).map(Vec::as_slice)
+    }
+
+    fn insert(&mut self, key: (SocketAddr, u64), payload: Vec<u8>) {
+        if self.capacity == 0 {
+            return;
+        }
+        while self.order.len() >= self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.responses.remove(&oldest);
+            }
+        }
+        self.order.push_back(key);
+        self.responses.insert(key, payload);
+    }
+}
+
+/// Datagram server where each datagram carries one `ClientMessage`
+pub struct UdpServer {
+    socket: UdpSocket,
+    config: UdpConfig,
+    is_running: Arc<AtomicBool>,
+    health: Health,
+    address: String,
+    cache: Mutex<ResponseCache>,
+}
+
+impl UdpServer {
+    /// Creates a new UDP server bound to `addr`
+    pub fn new(addr: &str, config: UdpConfig) -> io::Result<Self> {
+        let socket = UdpSocket::bind(addr)?;
+        let local_addr = socket.local_addr()?;
+        // Wake up periodically so `stop()` is noticed
+        socket.set_read_timeout(Some(Duration::from_millis(100)))?;
+        Ok(UdpServer {
+            socket,
+            cache: Mutex::new(ResponseCache::new(config.dedup_capacity)),
+            config,
+            is_running: Arc::new(AtomicBool::new(false)),
+            health: Health::new(ServingStatus::Starting),
+            address: local_addr.to_string(),
+        })
+    }
+
+    /// Returns the server's address
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Runs the server, answering datagrams until `stop()` is called
+    pub fn run(&self) -> io::Result<()> {
+        self.is_running.store(true, Ordering::SeqCst);
+        self.health.set(ServingStatus::Serving);
+        info!("UDP server is running on {}", self.address);
+
+        // One extra byte lets us tell an oversized datagram from one that fits exactly
+        let mut buffer = vec![0u8; self.config.max_datagram_size + 1];
+
+        while self.is_running.load(Ordering::SeqCst) {
+            match self.socket.recv_from(&mut buffer) {
+                Ok((bytes_read, peer)) => {
+                    if bytes_read > self.config.max_datagram_size {
+                        warn!(
+                            "Dropping datagram from {}: exceeds {} bytes",
+                            peer, self.config.max_datagram_size
+                        );
+                        continue;
+                    }
+                    info!("Received {} bytes from {}.", bytes_read, peer);
+                    self.handle_datagram(&buffer[..bytes_read], peer);
+                }
+                Err(ref e)
+                    if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut =>
+                {
+                    // Read timeout elapsed, loop around to check `is_running`
+                }
+                Err(e) => {
+                    error!("Error receiving datagram: {}", e);
+                }
+            }
+        }
+
+        self.health.set(ServingStatus::Stopped);
+        info!("UDP server stopped.");
+        Ok(())
+    }
+
+    /// Stops the server by setting the `is_running` flag to `false`
+    pub fn stop(&self) {
+        if self.is_running.swap(false, Ordering::SeqCst) {
+            info!("Shutdown signal sent to UDP server.");
+        } else {
+            warn!("UDP server was already stopped or not running.");
+        }
+    }
+
+    fn handle_datagram(&self, datagram: &[u8], peer: SocketAddr) {
+        let request = match ClientMessageWrapper::decode(datagram) {
+            Ok(request) => request,
+            Err(e) => {
+                error!("Failed to decode datagram from {}: {}", peer, e);
+                return;
+            }
+        };
+
+        // A request ID of 0 means the client did not ask for duplicate suppression
+        let key = (peer, request.request_id);
+        if request.request_id != 0 {
+            let cached = self.cache.lock().unwrap().get(&key).map(<[u8]>::to_vec);
+            if let Some(payload) = cached {
+                info!(
+                    "Duplicate request {} from {}, resending response",
+                    request.request_id, peer
+                );
+                self.send(&payload, peer);
+                return;
+            }
+        }
+
+        let Some(response) = handler::handle_request(
+            request,
+            &RequestContext {
+                validation: Some(&self.config.validation),
+                ..RequestContext::new(&self.health)
+            },
+        ) else {
+            return;
+        };
+        let payload = response.encode_to_vec();
+        if payload.len() > self.config.max_datagram_size {
+            warn!(
+                "Dropping response to {}: {} bytes exceeds {} bytes",
+                peer,
+                payload.len(),
+                self.config.max_datagram_size
+            );
+            return;
+        }
+
+        self.send(&payload, peer);
+        if key.1 != 0 {
+            self.cache.lock().unwrap().insert(key, payload);
+        }
+    }
+
+    fn send(&self, payload: &[u8], peer: SocketAddr) {
+        if let Err(e) = self.socket.send_to(payload, peer) {
+            error!("Failed to send response to {}: {}", peer, e);
+        }
+    }
+}
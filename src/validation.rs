@@ -0,0 +1,136 @@
+//! Constraints on request fields, checked before the handlers run.
+//!
+//! A request that breaks a rule is answered with an `Error` of code
+//! `INVALID_ARGUMENT` whose `field` names the offending field, e.g.
+//! `echo_message.content`.
+
+use crate::handler::ClientMessageWrapper;
+use crate::message::client_message;
+use std::{fmt, ops::RangeInclusive};
+
+/// Characters a string field may contain
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CharClass {
+    #[default]
+    Any,
+    /// Anything but control characters such as newlines or NUL
+    Printable,
+    /// ASCII letters, digits, punctuation and space
+    AsciiPrintable,
+    /// ASCII letters and digits
+    Alphanumeric,
+}
+
+impl CharClass {
+    pub fn contains(self, c: char) -> bool {
+        match self {
+            CharClass::Any => true,
+            CharClass::Printable => !c.is_control(),
+            CharClass::AsciiPrintable => matches!(c, ' '..='~'),
+            CharClass::Alphanumeric => c.is_ascii_alphanumeric(),
+        }
+    }
+}
+
+impl fmt::Display for CharClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CharClass::Any => "any",
+            CharClass::Printable => "printable",
+            CharClass::AsciiPrintable => "printable ASCII",
+            CharClass::Alphanumeric => "alphanumeric",
+        })
+    }
+}
+
+/// Validation rules for request fields. The default accepts everything the
+/// handlers can answer, i.e. all but `AddRequest`s whose sum overflows.
+#[derive(Debug, Clone, Default)]
+pub struct ValidationRules {
+    /// Longest `EchoMessage.content`, in characters
+    pub max_content_length: Option<usize>,
+    /// Characters `EchoMessage.content` may contain
+    pub content_chars: CharClass,
+    /// Range `AddRequest.a` and `AddRequest.b` must be in
+    pub add_operand_range: Option<RangeInclusive<i32>>,
+}
+
+/// A field that breaks a rule
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// Path of the field, e.g. `add_request.b`
+    pub field: &'static str,
+    pub reason: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.reason)
+    }
+}
+
+impl ValidationRules {
+    /// Checks the fields of `request`, returning the first violation
+    pub fn validate(&self, request: &ClientMessageWrapper) -> Result<(), Violation> {
+        match &request.message {
+            Some(client_message::Message::EchoMessage(echo)) => {
+                self.check_content("echo_message.content", &echo.content)
+            }
+            Some(client_message::Message::AddRequest(add)) => {
+                self.check_operand("add_request.a", add.a)?;
+                self.check_operand("add_request.b", add.b)?;
+                checked_sum(add.a, add.b).map(|_| ())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn check_content(&self, field: &'static str, value: &str) -> Result<(), Violation> {
+        if let Some(max) = self.max_content_length {
+            let length = value.chars().count();
+            if length > max {
+                return Err(Violation {
+                    field,
+                    reason: format!("{} characters, at most {} allowed", length, max),
+                });
+            }
+        }
+        if let Some(c) = value.chars().find(|c| !self.content_chars.contains(*c)) {
+            return Err(Violation {
+                field,
+                reason: format!(
+                    "character {:?} is not allowed, expected {} characters",
+                    c, self.content_chars
+                ),
+            });
+        }
+        Ok(())
+    }
+
+    fn check_operand(&self, field: &'static str, value: i32) -> Result<(), Violation> {
+        match &self.add_operand_range {
+            Some(range) if !range.contains(&value) => Err(Violation {
+                field,
+                reason: format!(
+                    "{} is out of range {}..={}",
+                    value,
+                    range.start(),
+                    range.end()
+                ),
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Adds `a` and `b`, refusing sums that overflow whatever the rules, as no
+/// response could carry them
+pub(crate) fn checked_sum(a: i32, b: i32) -> Result<i32, Violation> {
+    match a.checked_add(b) {
+        Some(sum) => Ok(sum),
+        None => Err(Violation {
+            field: "add_request",
+            reason: format!("{} + {} overflows a 32-bit integer", a, b),
+        }),
+    }
+}
@@ -0,0 +1,138 @@
+use std::{
+    io::{self, Read, Write},
+    mem,
+    net::{Shutdown, TcpStream},
+};
+use tracing::{debug, warn};
+use tungstenite::{Error as WsError, Message as WsMessage, WebSocket};
+
+// The whole stream is boxed inside `Stream`, so the variant sizes don't matter
+#[allow(clippy::large_enum_variant)]
+enum State {
+    /// Accepted, the HTTP upgrade has not been performed yet
+    Handshake(TcpStream),
+    Open(WebSocket<TcpStream>),
+    Closed,
+}
+
+/// Server side of a WebSocket connection carrying protobuf frames as binary
+/// messages.
+///
+/// Each `read` returns exactly one binary message and each `write` sends one,
+/// which matches how the TCP path treats a single read as a single request.
+/// The upgrade handshake runs on first use so it happens on the connection's
+/// own thread rather than in the accept loop.
+pub struct WebSocketStream {
+    state: State,
+}
+
+impl WebSocketStream {
+    pub(crate) fn new(stream: TcpStream) -> Self {
+        WebSocketStream {
+            state: State::Handshake(stream),
+        }
+    }
+
+    fn socket(&mut self) -> io::Result<&mut WebSocket<TcpStream>> {
+        if matches!(self.state, State::Handshake(_)) {
+            let State::Handshake(stream) = mem::replace(&mut self.state, State::Closed) else {
+                unreachable!();
+            };
+            let socket = tungstenite::accept(stream).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("WebSocket handshake failed: {}", e),
+                )
+            })?;
+            self.state = State::Open(socket);
+        }
+
+        match &mut self.state {
+            State::Open(socket) => Ok(socket),
+            _ => Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "WebSocket is closed",
+            )),
+        }
+    }
+
+    /// Clones the underlying TCP socket, e.g. to shut it down from another thread
+    pub(crate) fn try_clone_socket(&self) -> io::Result<TcpStream> {
+        match &self.state {
+            State::Handshake(stream) => stream.try_clone(),
+            State::Open(socket) => socket.get_ref().try_clone(),
+            State::Closed => Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "WebSocket is closed",
+            )),
+        }
+    }
+
+    pub fn shutdown(&mut self, how: Shutdown) -> io::Result<()> {
+        match mem::replace(&mut self.state, State::Closed) {
+            State::Handshake(stream) => stream.shutdown(how),
+            State::Open(mut socket) => {
+                let _ = socket.close(None);
+                let _ = socket.flush();
+                socket.get_ref().shutdown(how)
+            }
+            State::Closed => Ok(()),
+        }
+    }
+}
+
+impl Read for WebSocketStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if matches!(self.state, State::Closed) {
+            return Ok(0);
+        }
+
+        let socket = self.socket()?;
+        loop {
+            match socket.read() {
+                // Returning no bytes would read as the connection closing
+                Ok(WsMessage::Binary(data)) if data.is_empty() => {
+                    debug!("Ignoring empty WebSocket message");
+                }
+                Ok(WsMessage::Binary(data)) => {
+                    if data.len() > buf.len() {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("WebSocket message of {} bytes is too large", data.len()),
+                        ));
+                    }
+                    buf[..data.len()].copy_from_slice(&data);
+                    return Ok(data.len());
+                }
+                Ok(WsMessage::Close(_)) => return Ok(0),
+                Ok(WsMessage::Text(_)) => {
+                    warn!("Ignoring text WebSocket message, only binary frames are supported");
+                }
+                // Pings are answered by tungstenite itself
+                Ok(_) => {}
+                Err(WsError::ConnectionClosed | WsError::AlreadyClosed) => return Ok(0),
+                Err(e) => return Err(into_io_error(e)),
+            }
+        }
+    }
+}
+
+impl Write for WebSocketStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.socket()?
+            .send(WsMessage::Binary(buf.to_vec()))
+            .map_err(into_io_error)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.socket()?.flush().map_err(into_io_error)
+    }
+}
+
+fn into_io_error(e: WsError) -> io::Error {
+    match e {
+        WsError::Io(e) => e,
+        e => io::Error::new(io::ErrorKind::InvalidData, e.to_string()),
+    }
+}
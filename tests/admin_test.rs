@@ -0,0 +1,201 @@
+use embedded_recruitment_task::{
+    listener::Listener,
+    message::{client_message, server_message, AddRequest},
+    server::Server,
+};
+use log::LevelFilter;
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{SocketAddr, TcpStream},
+    sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+mod client;
+mod common;
+
+/// Line-oriented admin session
+struct Admin {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl Admin {
+    fn connect(addr: &str) -> Self {
+        let stream = TcpStream::connect(addr).expect("Failed to connect to admin");
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        Admin {
+            reader: BufReader::new(stream.try_clone().unwrap()),
+            writer: stream,
+        }
+    }
+
+    /// Sends a command and returns its output lines, or the error reason
+    fn command(&mut self, command: &str) -> Result<Vec<String>, String> {
+        writeln!(self.writer, "{}", command).expect("Failed to send command");
+        let mut lines = Vec::new();
+        loop {
+            let mut line = String::new();
+            self.reader
+                .read_line(&mut line)
+                .expect("Failed to read reply");
+            let line = line.trim_end().to_string();
+            if line == "OK" {
+                return Ok(lines);
+            }
+            if let Some(reason) = line.strip_prefix("ERR ") {
+                return Err(reason.to_string());
+            }
+            lines.push(line);
+        }
+    }
+}
+
+fn start_server() -> (Arc<Server>, String, JoinHandle<()>) {
+    start_configured_server(|server| server)
+}
+
+fn start_configured_server(
+    configure: impl FnOnce(Server) -> Server,
+) -> (Arc<Server>, String, JoinHandle<()>) {
+    let admin = Listener::bind_tcp("127.0.0.1:0").unwrap();
+    let admin_addr = admin.local_addr().unwrap();
+    let server = Server::new("127.0.0.1:0")
+        .and_then(|server| server.with_admin(admin))
+        .map(configure)
+        .expect("Failed to start server");
+    let (server, handle) = common::start(server);
+    (server, admin_addr, handle)
+}
+
+fn connect(server: &Server) -> client::Client {
+    let addr: SocketAddr = server.address().parse().unwrap();
+    let mut client = client::Client::new(&addr.ip().to_string(), addr.port().into(), 1000);
+    client.connect().expect("Failed to connect to the server");
+    client
+}
+
+fn add(client: &mut client::Client, a: i32, b: i32) -> i32 {
+    client
+        .send(client_message::Message::AddRequest(AddRequest { a, b }))
+        .expect("Failed to send message");
+    match client.receive().expect("Failed to receive").message {
+        Some(server_message::Message::AddResponse(response)) => response.result,
+        _ => panic!("Expected AddResponse, but received a different message"),
+    }
+}
+
+#[test]
+fn test_admin_list_inspect_and_kick() {
+    let (server, admin_addr, handle) = start_server();
+    let mut first = connect(&server);
+    let mut second = connect(&server);
+    assert_eq!(add(&mut first, 1, 2), 3);
+    assert_eq!(add(&mut second, 3, 4), 7);
+    assert_eq!(add(&mut second, 5, 6), 11);
+
+    let connections = server.connections();
+    assert_eq!(connections.len(), 2);
+    let (first_id, second_id) = (connections[0].id, connections[1].id);
+    assert_eq!(connections[1].requests, 2);
+
+    let mut admin = Admin::connect(&admin_addr);
+    let list = admin.command("list").unwrap();
+    assert_eq!(list.len(), 2);
+    assert!(list[0].starts_with(&format!("{} 127.0.0.1:", first_id)));
+    assert!(list[1].contains("requests=2"));
+
+    let details = admin.command(&format!("inspect {}", second_id)).unwrap();
+    assert!(details.contains(&format!("id: {}", second_id)));
+    assert!(details.contains(&"requests: 2".to_string()));
+
+    // The kicked client sees its connection end, the other one is unaffected
+    admin.command(&format!("kick {}", first_id)).unwrap();
+    assert!(first.receive().is_err());
+    thread::sleep(Duration::from_millis(100));
+    assert!(server.connection(first_id).is_none());
+    assert_eq!(admin.command("list").unwrap().len(), 1);
+    assert_eq!(add(&mut second, 1, 1), 2);
+
+    assert!(admin
+        .command(&format!("kick {}", first_id))
+        .unwrap_err()
+        .contains("no connection"));
+    assert!(admin.command("inspect x").is_err());
+    assert!(admin.command("kick").is_err());
+    assert!(admin
+        .command("reboot")
+        .unwrap_err()
+        .contains("unknown command"));
+
+    second.disconnect().expect("Failed to disconnect");
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_admin_drain() {
+    let (server, admin_addr, handle) = start_server();
+    let mut client = connect(&server);
+    assert_eq!(add(&mut client, 1, 2), 3);
+
+    let mut admin = Admin::connect(&admin_addr);
+    admin.command("drain").unwrap();
+    thread::sleep(Duration::from_millis(300));
+
+    // Open connections keep being served and the admin channel stays up
+    assert!(!handle.is_finished());
+    assert_eq!(add(&mut client, 2, 2), 4);
+    assert_eq!(admin.command("list").unwrap().len(), 1);
+
+    // The server finishes once the last connection is gone
+    client.disconnect().expect("Failed to disconnect");
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_admin_log_level() {
+    // Stands in for the reload handle of a tracing subscriber
+    let applied = Arc::new(Mutex::new(Vec::new()));
+    let (server, admin_addr, handle) = start_configured_server(|server| {
+        let applied = applied.clone();
+        server.on_log_level(move |level| {
+            if level == LevelFilter::Trace {
+                return Err(io::Error::new(io::ErrorKind::Unsupported, "too verbose"));
+            }
+            applied.lock().unwrap().push(level);
+            Ok(())
+        })
+    });
+    let mut admin = Admin::connect(&admin_addr);
+
+    let previous = admin.command("log-level").unwrap();
+    assert_eq!(admin.command("log-level debug").unwrap(), ["DEBUG"]);
+    assert_eq!(log::max_level(), LevelFilter::Debug);
+    assert_eq!(*applied.lock().unwrap(), [LevelFilter::Debug]);
+    assert!(admin.command("log-level loud").is_err());
+    assert_eq!(
+        admin.command("log-level trace"),
+        Err("too verbose".to_string())
+    );
+    assert_eq!(log::max_level(), LevelFilter::Debug);
+    admin
+        .command(&format!("log-level {}", previous[0]))
+        .unwrap();
+
+    writeln!(admin.writer, "quit").unwrap();
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
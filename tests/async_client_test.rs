@@ -0,0 +1,253 @@
+#![cfg(feature = "async")]
+
+use embedded_recruitment_task::{
+    async_client::AsyncClient,
+    client::Error,
+    framing::{self, Framing},
+    message::{client_message, server_message, ClientMessage, EchoMessage, ServerMessage},
+    server::Server,
+};
+use prost::Message;
+use socket2::{Domain, Socket, Type};
+use std::{
+    io::ErrorKind,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+};
+
+mod common;
+
+fn framed_server() -> Server {
+    Server::new("localhost:0")
+        .expect("Failed to start server")
+        .with_framing(Framing::LengthDelimited)
+}
+
+/// Framed echo server that answers each request after `delay(content)`,
+/// concurrently and so possibly out of order, and records the echoed
+/// contents
+async fn echo_server(delay: fn(&str) -> Duration) -> (SocketAddr, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let log = received.clone();
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (mut reader, writer) = stream.into_split();
+        let writer = Arc::new(tokio::sync::Mutex::new(writer));
+        let mut buffer = vec![0; 1024];
+        let mut pending = Vec::new();
+        loop {
+            let bytes_read = reader.read(&mut buffer).await.unwrap();
+            if bytes_read == 0 {
+                return;
+            }
+            pending.extend_from_slice(&buffer[..bytes_read]);
+            while let Some(frame) = framing::next_frame(&pending).unwrap() {
+                let request = ClientMessage::decode(&pending[frame.clone()]).unwrap();
+                pending.drain(..frame.end);
+                let Some(client_message::Message::EchoMessage(echo)) = request.message else {
+                    panic!("Expected an EchoMessage, got {:?}", request);
+                };
+                log.lock().unwrap().push(echo.content.clone());
+                let writer = writer.clone();
+                tokio::spawn(async move {
+                    tokio::time::sleep(delay(&echo.content)).await;
+                    let response = ServerMessage {
+                        message: Some(server_message::Message::EchoMessage(echo)),
+                        request_id: request.request_id,
+                    };
+                    let payload = response.encode_length_delimited_to_vec();
+                    let _ = writer.lock().await.write_all(&payload).await;
+                });
+            }
+        }
+    });
+    (addr, received)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_concurrent_calls_share_one_connection() {
+    let (server, handle) = common::start(framed_server());
+
+    let client = AsyncClient::connect(server.address().parse().unwrap())
+        .await
+        .expect("Failed to connect");
+    let calls: Vec<_> = (0..100)
+        .map(|i| {
+            let client = client.clone();
+            tokio::spawn(async move {
+                if i % 2 == 0 {
+                    assert_eq!(client.add(i, i).await.unwrap(), 2 * i);
+                } else {
+                    let content = format!("message {}", i);
+                    assert_eq!(client.echo(&content).await.unwrap(), content);
+                }
+            })
+        })
+        .collect();
+    for call in calls {
+        call.await.expect("Call panicked");
+    }
+    assert_eq!(server.connections().len(), 1);
+
+    drop(client);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(server.connections().is_empty());
+    server.stop();
+    handle.join().expect("Server thread panicked");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_cancelled_calls_against_real_server() {
+    let (server, handle) = common::start(framed_server());
+
+    let client = AsyncClient::connect(server.address().parse().unwrap())
+        .await
+        .expect("Failed to connect");
+    let calls: Vec<_> = (0..50)
+        .map(|i| {
+            let client = client.clone();
+            tokio::spawn(async move {
+                if i % 3 == 0 {
+                    // Either times out, whether or not the request was
+                    // written, or gets its own response
+                    let quick = client.with_timeout(Duration::ZERO);
+                    match quick.add(i, i).await {
+                        Ok(sum) => assert_eq!(sum, 2 * i),
+                        Err(e) => assert!(matches!(e, Error::Timeout), "{:?}", e),
+                    }
+                } else {
+                    let content = format!("message {}", i);
+                    assert_eq!(client.echo(&content).await.unwrap(), content);
+                }
+            })
+        })
+        .collect();
+    for call in calls {
+        call.await.expect("Call panicked");
+    }
+
+    // Responses to the cancelled calls were discarded, not handed to others
+    assert!(client.is_connected());
+    assert_eq!(client.add(20, 22).await.unwrap(), 42);
+
+    drop(client);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    server.stop();
+    handle.join().expect("Server thread panicked");
+}
+
+#[tokio::test]
+async fn test_connect_timeout() {
+    // Once the backlog of a listener that never accepts is full, further
+    // connection attempts hang
+    let socket = Socket::new(Domain::IPV4, Type::STREAM, None).unwrap();
+    socket
+        .bind(&"127.0.0.1:0".parse::<SocketAddr>().unwrap().into())
+        .unwrap();
+    socket.listen(0).unwrap();
+    let addr = socket.local_addr().unwrap().as_socket().unwrap();
+    let mut backlog = Vec::new();
+    let started = Instant::now();
+    let result = loop {
+        match AsyncClient::connect_timeout(addr, Duration::from_millis(200)).await {
+            Ok(client) => backlog.push(client),
+            Err(e) => break e,
+        }
+        assert!(backlog.len() < 100, "Connections never hung");
+    };
+    assert!(matches!(result, Error::Io(e) if e.kind() == ErrorKind::TimedOut));
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[tokio::test]
+async fn test_slow_call_does_not_block_fast_one() {
+    let (addr, _) = echo_server(|content| match content {
+        "slow" => Duration::from_millis(500),
+        _ => Duration::ZERO,
+    })
+    .await;
+    let client = AsyncClient::connect(addr).await.expect("Failed to connect");
+
+    let start = Instant::now();
+    let slow = tokio::spawn({
+        let client = client.clone();
+        async move { client.echo("slow").await }
+    });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(client.echo("fast").await.unwrap(), "fast");
+    assert!(
+        start.elapsed() < Duration::from_millis(250),
+        "Fast call waited {:?}",
+        start.elapsed()
+    );
+    assert!(!slow.is_finished());
+
+    assert_eq!(slow.await.unwrap().unwrap(), "slow");
+}
+
+#[tokio::test]
+async fn test_timeouts() {
+    let (addr, _) = echo_server(|content| match content {
+        "late" => Duration::from_millis(300),
+        _ => Duration::ZERO,
+    })
+    .await;
+    let client = AsyncClient::connect(addr).await.expect("Failed to connect");
+
+    let quick = client.clone().with_timeout(Duration::from_millis(100));
+    assert!(matches!(quick.echo("late").await, Err(Error::Timeout)));
+    assert_eq!(client.echo("on time").await.unwrap(), "on time");
+    assert!(client.is_connected());
+}
+
+#[tokio::test]
+async fn test_late_responses_to_cancelled_calls_are_discarded() {
+    let (addr, received) = echo_server(|content| match content {
+        "cancelled" => Duration::from_millis(200),
+        _ => Duration::ZERO,
+    })
+    .await;
+    let client = AsyncClient::connect(addr).await.expect("Failed to connect");
+
+    let cancelled = tokio::spawn({
+        let client = client.clone();
+        async move { client.echo("cancelled").await }
+    });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    cancelled.abort();
+
+    // Its response arrives with nobody waiting for it
+    tokio::time::sleep(Duration::from_millis(250)).await;
+    assert!(client.is_connected());
+    assert_eq!(client.echo("after").await.unwrap(), "after");
+    assert_eq!(*received.lock().unwrap(), ["cancelled", "after"]);
+}
+
+#[tokio::test]
+async fn test_disconnect() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        // Close the connection as soon as a request arrives
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let _ = stream.read(&mut [0; 1024]).await;
+    });
+
+    let client = AsyncClient::connect(addr).await.expect("Failed to connect");
+    let request = client_message::Message::EchoMessage(EchoMessage {
+        content: "hello".to_string(),
+    });
+    assert!(matches!(
+        client.request(request).await,
+        Err(Error::Disconnected)
+    ));
+    assert!(matches!(client.add(1, 2).await, Err(Error::Disconnected)));
+    assert!(!client.is_connected());
+}
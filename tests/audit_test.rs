@@ -0,0 +1,230 @@
+use embedded_recruitment_task::{
+    audit::{self, AuditEvent, AuditLog},
+    auth::CredentialStore,
+    filter::IpFilter,
+    listener::Listener,
+    message::{auth_request::Credential, client_message, server_message, AddRequest, AuthRequest},
+    policy::Policy,
+    server::Server,
+};
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{SocketAddr, TcpStream},
+    path::PathBuf,
+    thread,
+    time::Duration,
+};
+
+mod client;
+mod common;
+
+fn audit_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("ert-audit-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+/// Entries as `(connection, event, details)`
+fn entries(path: &PathBuf) -> Vec<(String, String, String)> {
+    std::fs::read_to_string(path)
+        .unwrap()
+        .lines()
+        .map(|line| {
+            let fields: Vec<&str> = line.split('\t').collect();
+            assert_eq!(fields.len(), 6, "malformed entry {:?}", line);
+            (
+                fields[2].to_string(),
+                fields[3].to_string(),
+                fields[4].to_string(),
+            )
+        })
+        .collect()
+}
+
+fn connect(server: &Server) -> client::Client {
+    let addr: SocketAddr = server.address().parse().unwrap();
+    let mut client = client::Client::new(&addr.ip().to_string(), addr.port().into(), 1000);
+    client.connect().expect("Failed to connect to the server");
+    client
+}
+
+fn send(client: &mut client::Client, message: client_message::Message) -> server_message::Message {
+    client.send(message).expect("Failed to send message");
+    client
+        .receive()
+        .expect("Failed to receive")
+        .message
+        .expect("Empty response")
+}
+
+fn authenticate(client: &mut client::Client, token: &str) -> server_message::Message {
+    send(
+        client,
+        client_message::Message::AuthRequest(AuthRequest {
+            identity: String::new(),
+            credential: Some(Credential::Token(token.to_string())),
+        }),
+    )
+}
+
+#[test]
+fn test_security_events_are_audited() {
+    let path = audit_path("events");
+    let admin = Listener::bind_tcp("127.0.0.1:0").unwrap();
+    let admin_addr = admin.local_addr().unwrap();
+    let policy = Policy::new()
+        .with_grant("dashboard", &["echo_message"])
+        .unwrap();
+    let server = Server::new("127.0.0.1:0")
+        .and_then(|server| server.with_admin(admin))
+        .expect("Failed to start server")
+        .with_credentials(CredentialStore::new().with_token("dashboard", "token"))
+        .with_policy(policy)
+        .with_audit_log(AuditLog::create(&path).expect("Failed to create audit log"));
+    let (server, handle) = common::start(server);
+
+    let mut client = connect(&server);
+    assert!(matches!(
+        authenticate(&mut client, "wrong"),
+        server_message::Message::Error(_)
+    ));
+    assert!(matches!(
+        authenticate(&mut client, "token"),
+        server_message::Message::AuthResponse(_)
+    ));
+    assert!(matches!(
+        send(
+            &mut client,
+            client_message::Message::AddRequest(AddRequest { a: 1, b: 1 })
+        ),
+        server_message::Message::Error(_)
+    ));
+    let connection = server.connections().remove(0);
+    let (id, peer) = (connection.id, connection.info.peer);
+
+    let admin = TcpStream::connect(&admin_addr).unwrap();
+    let mut reader = BufReader::new(admin.try_clone().unwrap());
+    writeln!(&admin, "kick {}", id).unwrap();
+    let mut line = String::new();
+    while line.trim_end() != "OK" {
+        line.clear();
+        reader.read_line(&mut line).unwrap();
+    }
+    assert!(client.receive().is_err());
+    client.disconnect().expect("Failed to disconnect");
+
+    let id = id.to_string();
+    assert_eq!(
+        entries(&path),
+        [
+            (id.clone(), "auth_failure".into(), "identity=\"\"".into()),
+            (
+                id.clone(),
+                "auth_success".into(),
+                "identity=\"dashboard\"".into()
+            ),
+            (
+                id.clone(),
+                "permission_denied".into(),
+                "identity=\"dashboard\" message_type=\"add_request\"".into()
+            ),
+            (
+                id.clone(),
+                "forced_disconnect".into(),
+                format!("peer=\"{}\"", peer)
+            ),
+            (
+                "-".into(),
+                "admin_command".into(),
+                format!("command=\"kick {}\" result=ok", id)
+            ),
+        ]
+    );
+    assert_eq!(audit::verify(&path).unwrap(), 5);
+
+    drop(admin);
+    server.stop();
+    assert!(handle.join().is_ok());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_hash_chain_detects_tampering() {
+    let path = audit_path("chain");
+    let log = AuditLog::create(&path).unwrap();
+    for identity in ["alice", "bob", "carol"] {
+        let event = AuditEvent::AuthFailure {
+            identity: identity.to_string(),
+        };
+        log.record(Some(1), &event).unwrap();
+    }
+    let head = log.head();
+    drop(log);
+    assert_eq!(audit::verify(&path).unwrap(), 3);
+
+    // Reopening extends the same chain
+    let log = AuditLog::create(&path).unwrap();
+    assert_eq!(log.head(), head);
+    log.record(
+        None,
+        &AuditEvent::ConnectionRefused {
+            peer: "192.0.2.1:4000".to_string(),
+        },
+    )
+    .unwrap();
+    drop(log);
+    assert_eq!(audit::verify(&path).unwrap(), 4);
+
+    // Rewriting an entry breaks the chain at that entry
+    let original = std::fs::read_to_string(&path).unwrap();
+    std::fs::write(&path, original.replacen("\"bob\"", "\"eve\"", 1)).unwrap();
+    let error = audit::verify(&path).unwrap_err();
+    assert_eq!(error.to_string(), "audit log line 2: hash mismatch");
+    assert!(AuditLog::create(&path).is_err());
+
+    // So does removing one
+    let lines: Vec<&str> = original.lines().collect();
+    std::fs::write(&path, format!("{}\n{}\n", lines[0], lines[2])).unwrap();
+    let error = audit::verify(&path).unwrap_err();
+    assert_eq!(error.to_string(), "audit log line 2: out of sequence");
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_refusal_flood_is_summarized() {
+    let path = audit_path("flood");
+    let server = Server::new("127.0.0.1:0")
+        .expect("Failed to start server")
+        .with_ip_filter(IpFilter::new().deny("127.0.0.1").unwrap())
+        .with_audit_log(AuditLog::create(&path).unwrap());
+    let (server, handle) = common::start(server);
+
+    for _ in 0..50 {
+        let mut stream = TcpStream::connect(server.address()).unwrap();
+        // Wait for the server to close the refused connection
+        let _ = std::io::Read::read(&mut stream, &mut [0; 1]);
+    }
+    // The count is written once the second is over and the server is idle
+    thread::sleep(Duration::from_millis(1500));
+
+    let entries = entries(&path);
+    let refused = entries
+        .iter()
+        .filter(|(_, event, _)| event == "connection_refused")
+        .count();
+    assert_eq!(refused, 10);
+    assert_eq!(
+        entries.last().unwrap(),
+        &(
+            "-".to_string(),
+            "suppressed".to_string(),
+            "event=connection_refused count=40".to_string()
+        )
+    );
+    assert_eq!(audit::verify(&path).unwrap(), 11);
+
+    server.stop();
+    assert!(handle.join().is_ok());
+    std::fs::remove_file(&path).unwrap();
+}
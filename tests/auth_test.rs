@@ -0,0 +1,221 @@
+use embedded_recruitment_task::{
+    auth::{self, CredentialStore},
+    message::{
+        auth_request::Credential, client_message, server_message, AuthChallengeRequest,
+        AuthRequest, EchoMessage, ErrorCode, HealthCheckRequest, ServerMessage,
+    },
+    server::Server,
+};
+use std::{net::SocketAddr, sync::Arc, thread::JoinHandle};
+
+mod client;
+mod common;
+
+const SECRET: &[u8] = b"device-secret";
+
+fn start_auth_server() -> (Arc<Server>, JoinHandle<()>) {
+    let store = CredentialStore::new()
+        .with_token("ops", "s3cret-token")
+        .with_secret("device-7", SECRET);
+    common::start(
+        Server::new("localhost:0")
+            .expect("Failed to start server")
+            .with_credentials(store),
+    )
+}
+
+fn stop_server(server: Arc<Server>, handle: JoinHandle<()>) {
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+fn connect(server: &Server) -> client::Client {
+    let addr: SocketAddr = server.address().parse().unwrap();
+    let mut client = client::Client::new(&addr.ip().to_string(), addr.port().into(), 1000);
+    client.connect().expect("Failed to connect to the server");
+    client
+}
+
+fn request(client: &mut client::Client, message: client_message::Message) -> ServerMessage {
+    client.send(message).expect("Failed to send message");
+    client.receive().expect("Failed to receive")
+}
+
+fn echo(client: &mut client::Client) -> ServerMessage {
+    request(
+        client,
+        client_message::Message::EchoMessage(EchoMessage {
+            content: "hello".to_string(),
+        }),
+    )
+}
+
+fn authenticate(
+    client: &mut client::Client,
+    identity: &str,
+    credential: Credential,
+) -> ServerMessage {
+    request(
+        client,
+        client_message::Message::AuthRequest(AuthRequest {
+            identity: identity.to_string(),
+            credential: Some(credential),
+        }),
+    )
+}
+
+fn error_code(response: &ServerMessage) -> Option<ErrorCode> {
+    match &response.message {
+        Some(server_message::Message::Error(error)) => Some(error.code()),
+        _ => None,
+    }
+}
+
+fn authenticated_as(response: &ServerMessage) -> Option<&str> {
+    match &response.message {
+        Some(server_message::Message::AuthResponse(response)) => Some(&response.identity),
+        _ => None,
+    }
+}
+
+#[test]
+fn test_bearer_token() {
+    let (server, handle) = start_auth_server();
+    let mut client = connect(&server);
+
+    assert_eq!(
+        error_code(&echo(&mut client)),
+        Some(ErrorCode::Unauthenticated)
+    );
+    // Probes do not need credentials
+    assert!(matches!(
+        request(
+            &mut client,
+            client_message::Message::HealthCheckRequest(HealthCheckRequest {})
+        )
+        .message,
+        Some(server_message::Message::HealthCheckResponse(_))
+    ));
+
+    let response = authenticate(&mut client, "", Credential::Token("guess".to_string()));
+    assert_eq!(error_code(&response), Some(ErrorCode::Unauthenticated));
+    assert_eq!(
+        error_code(&echo(&mut client)),
+        Some(ErrorCode::Unauthenticated)
+    );
+
+    let response = authenticate(
+        &mut client,
+        "",
+        Credential::Token("s3cret-token".to_string()),
+    );
+    assert_eq!(authenticated_as(&response), Some("ops"));
+    assert!(matches!(
+        echo(&mut client).message,
+        Some(server_message::Message::EchoMessage(_))
+    ));
+    assert_eq!(server.connections()[0].identity.as_deref(), Some("ops"));
+
+    // Another connection has to authenticate on its own
+    let mut other = connect(&server);
+    assert_eq!(
+        error_code(&echo(&mut other)),
+        Some(ErrorCode::Unauthenticated)
+    );
+
+    client.disconnect().expect("Failed to disconnect");
+    other.disconnect().expect("Failed to disconnect");
+    stop_server(server, handle);
+}
+
+#[test]
+fn test_hmac_challenge() {
+    let (server, handle) = start_auth_server();
+    let mut client = connect(&server);
+
+    let challenge = |client: &mut client::Client| match request(
+        client,
+        client_message::Message::AuthChallengeRequest(AuthChallengeRequest {}),
+    )
+    .message
+    {
+        Some(server_message::Message::AuthChallenge(challenge)) => challenge.nonce,
+        _ => panic!("Expected AuthChallenge, but received a different message"),
+    };
+
+    // Wrong secret
+    let nonce = challenge(&mut client);
+    assert_eq!(nonce.len(), auth::NONCE_LEN);
+    let mac = auth::challenge_response(b"wrong", &nonce);
+    let response = authenticate(&mut client, "device-7", Credential::Hmac(mac));
+    assert_eq!(error_code(&response), Some(ErrorCode::Unauthenticated));
+
+    let nonce = challenge(&mut client);
+    let mac = auth::challenge_response(SECRET, &nonce);
+    let response = authenticate(&mut client, "device-7", Credential::Hmac(mac.clone()));
+    assert_eq!(authenticated_as(&response), Some("device-7"));
+    assert!(matches!(
+        echo(&mut client).message,
+        Some(server_message::Message::EchoMessage(_))
+    ));
+
+    // Nonces are single use, so a captured answer cannot be replayed
+    let mut replayed = connect(&server);
+    let response = authenticate(&mut replayed, "device-7", Credential::Hmac(mac.clone()));
+    assert_eq!(error_code(&response), Some(ErrorCode::Unauthenticated));
+    let response = authenticate(&mut client, "device-7", Credential::Hmac(mac));
+    assert_eq!(error_code(&response), Some(ErrorCode::Unauthenticated));
+    // ...and the failed attempt signed the connection out
+    assert_eq!(
+        error_code(&echo(&mut client)),
+        Some(ErrorCode::Unauthenticated)
+    );
+
+    client.disconnect().expect("Failed to disconnect");
+    replayed.disconnect().expect("Failed to disconnect");
+    stop_server(server, handle);
+}
+
+#[test]
+fn test_credential_file() {
+    let path = std::env::temp_dir().join(format!("ert-credentials-{}", std::process::id()));
+    std::fs::write(
+        &path,
+        "# operators\ntoken ops s3cret-token\n\nsecret device-7 device-secret\n",
+    )
+    .unwrap();
+    let store = CredentialStore::from_file(&path).expect("Failed to load credentials");
+    assert_eq!(store.authenticate_token("s3cret-token"), Some("ops"));
+    assert_eq!(store.authenticate_token("other"), None);
+    let mac = auth::challenge_response(SECRET, b"nonce");
+    assert!(store.verify_hmac("device-7", b"nonce", &mac));
+    assert!(!store.verify_hmac("ops", b"nonce", &mac));
+
+    std::fs::write(&path, "token ops\n").unwrap();
+    let error = CredentialStore::from_file(&path)
+        .err()
+        .expect("Expected an error");
+    assert!(error.to_string().starts_with("line 1:"));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_auth_disabled() {
+    let (server, handle) = common::start(Server::new("localhost:0").unwrap());
+    let mut client = connect(&server);
+    assert!(matches!(
+        echo(&mut client).message,
+        Some(server_message::Message::EchoMessage(_))
+    ));
+    let response = authenticate(
+        &mut client,
+        "",
+        Credential::Token("s3cret-token".to_string()),
+    );
+    assert_eq!(error_code(&response), Some(ErrorCode::Unsupported));
+    client.disconnect().expect("Failed to disconnect");
+    stop_server(server, handle);
+}
@@ -0,0 +1,62 @@
+//! Fixtures shared by the integration tests
+
+#[cfg(feature = "http")]
+use embedded_recruitment_task::{
+    health::HealthEndpoint, http::HttpGateway, metrics::MetricsExporter,
+};
+use embedded_recruitment_task::{server::Server, udp::UdpServer};
+use std::{
+    io,
+    sync::Arc,
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+/// Anything served by a blocking `run` until it is stopped
+pub trait Run: Send + Sync + 'static {
+    fn run(&self) -> io::Result<()>;
+}
+
+impl Run for Server {
+    fn run(&self) -> io::Result<()> {
+        Server::run(self)
+    }
+}
+
+impl Run for UdpServer {
+    fn run(&self) -> io::Result<()> {
+        UdpServer::run(self)
+    }
+}
+
+#[cfg(feature = "http")]
+impl Run for HealthEndpoint {
+    fn run(&self) -> io::Result<()> {
+        HealthEndpoint::run(self)
+    }
+}
+
+#[cfg(feature = "http")]
+impl Run for HttpGateway {
+    fn run(&self) -> io::Result<()> {
+        HttpGateway::run(self)
+    }
+}
+
+#[cfg(feature = "http")]
+impl Run for MetricsExporter {
+    fn run(&self) -> io::Result<()> {
+        MetricsExporter::run(self)
+    }
+}
+
+/// Runs `server` on a thread of its own and gives it time to start
+pub fn start<S: Run>(server: S) -> (Arc<S>, JoinHandle<()>) {
+    let server = Arc::new(server);
+    let handle = {
+        let server = server.clone();
+        thread::spawn(move || server.run().expect("Server encountered an error"))
+    };
+    thread::sleep(Duration::from_millis(100));
+    (server, handle)
+}
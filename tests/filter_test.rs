@@ -0,0 +1,126 @@
+use embedded_recruitment_task::{
+    filter::IpFilter,
+    message::{client_message, server_message, AddRequest},
+    server::Server,
+};
+use std::{
+    io::Read,
+    net::{IpAddr, SocketAddr, TcpStream},
+    time::Duration,
+};
+
+mod client;
+mod common;
+
+/// Whether the server closes a new connection without serving it
+fn is_refused(server: &Server) -> bool {
+    let mut stream = TcpStream::connect(server.address()).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_millis(500)))
+        .unwrap();
+    // A served connection stays open and silent, a refused one reads EOF
+    matches!(stream.read(&mut [0u8; 1]), Ok(0))
+}
+
+fn add(server: &Server) -> i32 {
+    let addr: SocketAddr = server.address().parse().unwrap();
+    let mut client = client::Client::new(&addr.ip().to_string(), addr.port().into(), 1000);
+    client.connect().expect("Failed to connect to the server");
+    client
+        .send(client_message::Message::AddRequest(AddRequest {
+            a: 2,
+            b: 2,
+        }))
+        .expect("Failed to send message");
+    let result = match client.receive().expect("Failed to receive").message {
+        Some(server_message::Message::AddResponse(response)) => response.result,
+        _ => panic!("Expected AddResponse, but received a different message"),
+    };
+    client.disconnect().expect("Failed to disconnect");
+    result
+}
+
+#[test]
+fn test_ip_filter_rules() {
+    let ip = |addr: &str| addr.parse::<IpAddr>().unwrap();
+    let filter = IpFilter::new()
+        .allow("10.0.0.0/8")
+        .and_then(|filter| filter.deny("10.1.0.0/16"))
+        .and_then(|filter| filter.allow("fd00::/8"))
+        .expect("Invalid filter");
+    assert!(filter.allows(ip("10.2.3.4")));
+    assert!(!filter.allows(ip("10.1.3.4")));
+    assert!(!filter.allows(ip("192.168.1.1")));
+    assert!(filter.allows(ip("::ffff:10.2.3.4")));
+    assert!(filter.allows(ip("fd12::1")));
+
+    // Without allowed networks everything that is not denied is accepted
+    let filter = IpFilter::new().deny("192.168.1.7").unwrap();
+    assert!(!filter.allows(ip("192.168.1.7")));
+    assert!(filter.allows(ip("192.168.1.8")));
+    assert!(IpFilter::new().allows(ip("203.0.113.9")));
+
+    assert!(IpFilter::new().allow("10.0.0.0/33").is_err());
+    assert!(IpFilter::new().deny("localhost").is_err());
+}
+
+#[test]
+fn test_refused_after_accept() {
+    let filter = IpFilter::new().deny("127.0.0.0/8").unwrap();
+    let (server, handle) = common::start(
+        Server::new("127.0.0.1:0")
+            .expect("Failed to start server")
+            .with_ip_filter(filter),
+    );
+
+    assert!(is_refused(&server));
+    assert!(is_refused(&server));
+    let metrics = server.metrics();
+    assert_eq!(metrics.connections_rejected, 2);
+    assert_eq!(metrics.connections_accepted, 0);
+    assert!(metrics
+        .to_prometheus()
+        .contains("server_connections_rejected_total 2\n"));
+
+    // Replacing the filter takes effect for the next connection
+    server.set_ip_filter(Some(IpFilter::new().allow("127.0.0.1").unwrap()));
+    assert_eq!(add(&server), 4);
+
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_reload_from_file() {
+    let path = std::env::temp_dir().join(format!("ert-filter-{}", std::process::id()));
+    std::fs::write(&path, "# devices only\nallow 10.20.0.0/16\n").unwrap();
+    let (server, handle) = common::start(
+        Server::new("127.0.0.1:0")
+            .expect("Failed to start server")
+            .with_ip_filter(IpFilter::from_file(&path).expect("Failed to load filter")),
+    );
+    assert!(is_refused(&server));
+
+    std::fs::write(&path, "allow 10.20.0.0/16\nallow 127.0.0.1\n").unwrap();
+    server.reload_ip_filter().expect("Failed to reload filter");
+    assert_eq!(add(&server), 4);
+
+    // A broken file keeps the previous filter
+    std::fs::write(&path, "allow everyone\n").unwrap();
+    let error = server.reload_ip_filter().expect_err("Expected an error");
+    assert_eq!(error.to_string(), "line 1: invalid network \"everyone\"");
+    assert_eq!(add(&server), 4);
+
+    server.set_ip_filter(None);
+    assert!(server.reload_ip_filter().is_err());
+
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+    std::fs::remove_file(&path).unwrap();
+}
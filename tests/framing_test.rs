@@ -0,0 +1,133 @@
+use embedded_recruitment_task::{
+    client::Client,
+    framing::{self, Framing, MAX_FRAME_SIZE},
+    message::{
+        client_message, server_message, AddRequest, ClientMessage, EchoMessage, ServerMessage,
+    },
+    pool::{Pool, PoolConfig},
+    server::Server,
+};
+use prost::Message;
+use std::{
+    io::{Read, Write},
+    net::TcpStream,
+    thread,
+    time::Duration,
+};
+
+mod common;
+
+fn framed_server() -> Server {
+    Server::new("localhost:0")
+        .expect("Failed to start server")
+        .with_framing(Framing::LengthDelimited)
+}
+
+fn echo(request_id: u64, content: &str) -> ClientMessage {
+    ClientMessage {
+        message: Some(client_message::Message::EchoMessage(EchoMessage {
+            content: content.to_string(),
+        })),
+        request_id,
+    }
+}
+
+/// Reads `count` framed responses
+fn read_responses(stream: &mut TcpStream, count: usize) -> Vec<ServerMessage> {
+    let mut responses = Vec::new();
+    let mut received = Vec::new();
+    let mut buffer = [0; 1024];
+    while responses.len() < count {
+        let bytes_read = stream.read(&mut buffer).expect("Failed to read");
+        assert_ne!(bytes_read, 0, "Connection closed");
+        received.extend_from_slice(&buffer[..bytes_read]);
+        while let Some(frame) = framing::next_frame(&received).unwrap() {
+            responses.push(ServerMessage::decode(&received[frame.clone()]).unwrap());
+            received.drain(..frame.end);
+        }
+    }
+    responses
+}
+
+#[test]
+fn test_length_delimited_server() {
+    let (server, handle) = common::start(framed_server());
+    let mut stream = TcpStream::connect(server.address()).expect("Failed to connect");
+
+    // Two requests in one write
+    let mut payload = echo(1, "first").encode_length_delimited_to_vec();
+    let add = ClientMessage {
+        message: Some(client_message::Message::AddRequest(AddRequest {
+            a: 2,
+            b: 3,
+        })),
+        request_id: 2,
+    };
+    payload.extend(add.encode_length_delimited_to_vec());
+    stream.write_all(&payload).unwrap();
+    let responses = read_responses(&mut stream, 2);
+    assert_eq!(responses[0].request_id, 1);
+    assert!(matches!(
+        &responses[0].message,
+        Some(server_message::Message::EchoMessage(echo)) if echo.content == "first"
+    ));
+    assert_eq!(responses[1].request_id, 2);
+    assert!(matches!(
+        &responses[1].message,
+        Some(server_message::Message::AddResponse(sum)) if sum.result == 5
+    ));
+
+    // One request over several writes
+    let payload = echo(3, "split").encode_length_delimited_to_vec();
+    for part in payload.chunks(3) {
+        stream.write_all(part).unwrap();
+        thread::sleep(Duration::from_millis(20));
+    }
+    let responses = read_responses(&mut stream, 1);
+    assert_eq!(responses[0].request_id, 3);
+
+    drop(stream);
+    thread::sleep(Duration::from_millis(100));
+    server.stop();
+    handle.join().expect("Server thread panicked");
+}
+
+#[test]
+fn test_length_delimited_client_and_pool() {
+    let (server, handle) = common::start(framed_server());
+    let addr = server.address().parse().unwrap();
+
+    let mut client = Client::new(addr).with_framing(Framing::LengthDelimited);
+    client.connect().expect("Failed to connect");
+    assert_eq!(client.echo("framed").unwrap(), "framed");
+    assert_eq!(client.add(2, 3).unwrap(), 5);
+
+    let pool = Pool::new(PoolConfig::default(), move || {
+        Client::new(addr).with_framing(Framing::LengthDelimited)
+    })
+    .expect("Failed to create pool");
+    assert_eq!(pool.get().unwrap().add(20, 22).unwrap(), 42);
+
+    drop(client);
+    drop(pool);
+    thread::sleep(Duration::from_millis(100));
+    server.stop();
+    handle.join().expect("Server thread panicked");
+}
+
+#[test]
+fn test_next_frame() {
+    let frame = echo(1, "hello").encode_length_delimited_to_vec();
+    let length = frame.len() - 1;
+    assert_eq!(framing::next_frame(&frame).unwrap(), Some(1..1 + length));
+    assert_eq!(framing::next_frame(&frame[..length]).unwrap(), None);
+    assert_eq!(framing::next_frame(&[]).unwrap(), None);
+
+    // An unfinished length prefix
+    assert_eq!(framing::next_frame(&[0x80, 0x80]).unwrap(), None);
+
+    let mut oversized = Vec::new();
+    prost::encode_length_delimiter(MAX_FRAME_SIZE + 1, &mut oversized).unwrap();
+    assert!(framing::next_frame(&oversized).is_err());
+    assert!(framing::next_frame(&[0xff; 11]).is_err());
+}
@@ -0,0 +1,129 @@
+#![cfg(unix)]
+
+use embedded_recruitment_task::{
+    listener::Listener,
+    message::{client_message, server_message, AddRequest, ClientMessage, ServerMessage},
+    server::Server,
+};
+use prost::Message;
+use std::{
+    io::{Read, Write},
+    net::{SocketAddr, TcpStream},
+    os::unix::net::UnixStream,
+    path::PathBuf,
+};
+
+mod common;
+
+fn socket_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("ert-{}-{}.sock", name, std::process::id()))
+}
+
+fn add<S: Read + Write>(stream: &mut S, a: i32, b: i32) -> i32 {
+    let request = ClientMessage {
+        message: Some(client_message::Message::AddRequest(AddRequest { a, b })),
+        ..Default::default()
+    };
+    stream
+        .write_all(&request.encode_to_vec())
+        .expect("Failed to send message");
+
+    let mut buffer = vec![0u8; 65536];
+    let bytes_read = stream.read(&mut buffer).expect("Failed to read response");
+    match ServerMessage::decode(&buffer[..bytes_read])
+        .expect("Failed to decode response")
+        .message
+    {
+        Some(server_message::Message::AddResponse(response)) => response.result,
+        _ => panic!("Expected AddResponse, but received a different message"),
+    }
+}
+
+#[test]
+fn test_listener_handoff() {
+    let handoff_path = socket_path("handoff");
+    let unix_path = socket_path("handoff-unix");
+    let listeners = vec![
+        Listener::bind_tcp("127.0.0.1:0").unwrap(),
+        Listener::bind_unix(&unix_path, &Default::default()).unwrap(),
+    ];
+    let old = Server::from_listeners(listeners)
+        .and_then(|server| server.with_handoff(&handoff_path))
+        .expect("Failed to start server");
+    let (old, old_handle) = common::start(old);
+
+    let addr: SocketAddr = old.address().parse().unwrap();
+    let mut existing = TcpStream::connect(addr).unwrap();
+    assert_eq!(add(&mut existing, 1, 1), 2);
+
+    // The new process takes over the same sockets
+    let new = Server::from_handoff(&handoff_path).expect("Failed to take over");
+    assert_eq!(new.addresses(), old.addresses());
+    let (new, new_handle) = common::start(new);
+
+    // New connections go to the new server over TCP and the Unix socket
+    let mut fresh = TcpStream::connect(addr).unwrap();
+    assert_eq!(add(&mut fresh, 2, 3), 5);
+    let mut fresh_unix = UnixStream::connect(&unix_path).unwrap();
+    assert_eq!(add(&mut fresh_unix, 4, 5), 9);
+
+    // The old server drains: it keeps serving the connection it already had
+    assert_eq!(add(&mut existing, 20, 22), 42);
+    assert!(!old_handle.is_finished());
+    drop(existing);
+    assert!(
+        old_handle.join().is_ok(),
+        "Old server thread panicked or failed to join"
+    );
+
+    // Dropping the old server leaves the socket files to the new one
+    drop(old);
+    assert!(unix_path.exists());
+    assert!(!handoff_path.exists());
+    let mut after = UnixStream::connect(&unix_path).unwrap();
+    assert_eq!(add(&mut after, 6, 7), 13);
+
+    drop(fresh);
+    drop(fresh_unix);
+    drop(after);
+    new.stop();
+    assert!(
+        new_handle.join().is_ok(),
+        "New server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_handoff_can_be_repeated() {
+    let handoff_path = socket_path("handoff-repeat");
+    let first = Server::new("127.0.0.1:0")
+        .and_then(|server| server.with_handoff(&handoff_path))
+        .expect("Failed to start server");
+    let (_first, first_handle) = common::start(first);
+
+    // The second server offers the listeners on the same path right away
+    let second = Server::from_handoff(&handoff_path)
+        .and_then(|server| server.with_handoff(&handoff_path))
+        .expect("Failed to take over");
+    first_handle.join().unwrap();
+    let (_second, second_handle) = common::start(second);
+
+    let third = Server::from_handoff(&handoff_path).expect("Failed to take over again");
+    second_handle.join().unwrap();
+    let (third, third_handle) = common::start(third);
+
+    let mut stream = TcpStream::connect(third.address()).unwrap();
+    assert_eq!(add(&mut stream, 3, 4), 7);
+
+    drop(stream);
+    third.stop();
+    assert!(
+        third_handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_no_handoff_without_a_server() {
+    assert!(Server::from_handoff(socket_path("handoff-missing")).is_err());
+}
@@ -0,0 +1,111 @@
+use embedded_recruitment_task::{
+    message::{client_message, server_message, HealthCheckRequest, ServingStatus},
+    server::Server,
+};
+use std::net::SocketAddr;
+
+mod client;
+mod common;
+
+fn create_server() -> Server {
+    Server::new("localhost:0").expect("Failed to start server")
+}
+
+fn connect(server: &Server) -> client::Client {
+    let addr: SocketAddr = server.address().parse().unwrap();
+    let mut client = client::Client::new(&addr.ip().to_string(), addr.port().into(), 1000);
+    client.connect().expect("Failed to connect to the server");
+    client
+}
+
+fn health_check(client: &mut client::Client) -> (ServingStatus, bool) {
+    client
+        .send(client_message::Message::HealthCheckRequest(
+            HealthCheckRequest {},
+        ))
+        .expect("Failed to send message");
+    match client.receive().expect("Failed to receive").message {
+        Some(server_message::Message::HealthCheckResponse(response)) => {
+            (response.status(), response.ready)
+        }
+        _ => panic!("Expected HealthCheckResponse, but received a different message"),
+    }
+}
+
+#[test]
+fn test_health_check_follows_lifecycle() {
+    let server = create_server();
+    assert_eq!(server.status(), ServingStatus::Starting);
+
+    let (server, handle) = common::start(server);
+    let mut client = connect(&server);
+    assert_eq!(health_check(&mut client), (ServingStatus::Serving, true));
+
+    // Open connections can still ask while the server drains
+    server.drain();
+    assert_eq!(health_check(&mut client), (ServingStatus::Draining, false));
+
+    client.disconnect().expect("Failed to disconnect");
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+    assert_eq!(server.status(), ServingStatus::Stopped);
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn test_health_endpoint() {
+    use embedded_recruitment_task::health::HealthEndpoint;
+    use std::{
+        io::{Read, Write},
+        net::TcpStream,
+    };
+
+    let server = create_server();
+    let (endpoint, endpoint_handle) = common::start(
+        HealthEndpoint::new("127.0.0.1:0", server.health()).expect("Failed to start endpoint"),
+    );
+
+    let probe = |method: &str, path: &str| {
+        let mut stream = TcpStream::connect(endpoint.address()).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: 0\r\n\r\n",
+            method, path
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    };
+
+    // Alive but not ready before `run()`
+    assert!(probe("GET", "/healthz").starts_with("HTTP/1.1 200"));
+    let response = probe("GET", "/readyz");
+    assert!(response.starts_with("HTTP/1.1 503"));
+    assert!(response.ends_with("starting\n"));
+
+    let (server, handle) = common::start(server);
+    let response = probe("GET", "/readyz");
+    assert!(response.starts_with("HTTP/1.1 200"));
+    assert!(response.ends_with("serving\n"));
+
+    // Once it has been answered, the connection keeps the server draining
+    let mut client = connect(&server);
+    health_check(&mut client);
+    server.drain();
+    let response = probe("GET", "/readyz");
+    assert!(response.starts_with("HTTP/1.1 503"));
+    assert!(response.ends_with("draining\n"));
+    assert!(probe("GET", "/healthz").starts_with("HTTP/1.1 200"));
+
+    drop(client);
+    assert!(handle.join().is_ok());
+    assert!(probe("GET", "/healthz").starts_with("HTTP/1.1 503"));
+    assert!(probe("POST", "/readyz").starts_with("HTTP/1.1 405"));
+    assert!(probe("GET", "/metrics").starts_with("HTTP/1.1 404"));
+
+    endpoint.stop();
+    assert!(endpoint_handle.join().is_ok());
+}
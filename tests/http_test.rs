@@ -0,0 +1,151 @@
+#![cfg(feature = "http")]
+
+use embedded_recruitment_task::{http::HttpGateway, validation::ValidationRules};
+use serde_json::{json, Value};
+use std::{
+    io::{Read, Write},
+    net::TcpStream,
+};
+
+mod common;
+
+fn create_gateway() -> HttpGateway {
+    HttpGateway::new("127.0.0.1:0").expect("Failed to start gateway")
+}
+
+/// Sends a single HTTP/1.1 request and returns the status code and JSON body
+fn request(gateway: &HttpGateway, method: &str, path: &str, body: &str) -> (u16, Value) {
+    let mut stream = TcpStream::connect(gateway.address()).expect("Failed to connect");
+    write!(
+        stream,
+        "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        method,
+        path,
+        body.len(),
+        body
+    )
+    .expect("Failed to send request");
+
+    let mut response = String::new();
+    stream
+        .read_to_string(&mut response)
+        .expect("Failed to read response");
+
+    let (head, body) = response.split_once("\r\n\r\n").expect("Malformed response");
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .expect("Missing status code");
+    (
+        status,
+        serde_json::from_str(body).expect("Body is not JSON"),
+    )
+}
+
+#[test]
+fn test_http_echo_and_add() {
+    let (gateway, handle) = common::start(create_gateway());
+
+    let (status, body) = request(&gateway, "POST", "/echo", r#"{"content": "Hello, curl!"}"#);
+    assert_eq!(status, 200);
+    assert_eq!(
+        body["message"]["echo_message"]["content"],
+        json!("Hello, curl!")
+    );
+
+    let (status, body) = request(&gateway, "POST", "/add", r#"{"a": 10, "b": 32}"#);
+    assert_eq!(status, 200);
+    assert_eq!(body["message"]["add_response"]["result"], json!(42));
+
+    // Omitted fields take their protobuf default
+    let (status, body) = request(&gateway, "POST", "/add", r#"{"a": 5}"#);
+    assert_eq!(status, 200);
+    assert_eq!(body["message"]["add_response"]["result"], json!(5));
+
+    gateway.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Gateway thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_http_full_client_message() {
+    let (gateway, handle) = common::start(create_gateway());
+
+    let (status, body) = request(
+        &gateway,
+        "POST",
+        "/message",
+        r#"{"request_id": 9, "message": {"add_request": {"a": 1, "b": 2}}}"#,
+    );
+    assert_eq!(status, 200);
+    assert_eq!(body["request_id"], json!(9));
+    assert_eq!(body["message"]["add_response"]["result"], json!(3));
+
+    gateway.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Gateway thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_http_errors() {
+    let (gateway, handle) = common::start(create_gateway());
+
+    let (status, body) = request(&gateway, "POST", "/subtract", "{}");
+    assert_eq!(status, 404);
+    assert!(body["error"].is_string());
+
+    let (status, _) = request(&gateway, "GET", "/echo", "");
+    assert_eq!(status, 405);
+
+    let (status, _) = request(&gateway, "POST", "/add", "not json");
+    assert_eq!(status, 400);
+
+    let (status, _) = request(&gateway, "POST", "/add", r#"{"a": "ten"}"#);
+    assert_eq!(status, 400);
+
+    let (status, _) = request(&gateway, "POST", "/message", "{}");
+    assert_eq!(status, 400);
+
+    gateway.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Gateway thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_http_validation() {
+    let rules = ValidationRules {
+        max_content_length: Some(8),
+        ..ValidationRules::default()
+    };
+    let (gateway, handle) = common::start(
+        HttpGateway::new("127.0.0.1:0")
+            .expect("Failed to start gateway")
+            .with_validation(rules),
+    );
+
+    let (status, body) = request(&gateway, "POST", "/echo", r#"{"content": "far too long"}"#);
+    assert_eq!(status, 200);
+    assert_eq!(
+        body["message"]["error"]["field"],
+        json!("echo_message.content")
+    );
+
+    // Overflowing sums are refused rather than wrapped
+    let (status, body) = request(&gateway, "POST", "/add", r#"{"a": 2147483647, "b": 1}"#);
+    assert_eq!(status, 200);
+    assert_eq!(body["message"]["error"]["field"], json!("add_request"));
+
+    gateway.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Gateway thread panicked or failed to join"
+    );
+}
@@ -0,0 +1,82 @@
+use embedded_recruitment_task::{
+    client::{Client, Error},
+    message::{client_message, server_message, ErrorCode, HealthCheckRequest, ServingStatus},
+    server::Server,
+    validation::ValidationRules,
+};
+use std::{net::SocketAddr, time::Duration};
+
+mod common;
+
+fn address(server: &Server) -> SocketAddr {
+    server.address().parse().unwrap()
+}
+
+#[test]
+fn test_typed_requests() {
+    let (server, handle) = common::start(Server::new("localhost:0").expect("Failed to start"));
+    let mut client = Client::new(address(&server)).with_timeout(Duration::from_secs(5));
+
+    assert!(matches!(client.echo("hello"), Err(Error::NotConnected)));
+    client.connect().expect("Failed to connect");
+    assert!(client.is_connected());
+
+    assert_eq!(client.echo("hello").unwrap(), "hello");
+    assert_eq!(client.echo("").unwrap(), "");
+    assert_eq!(client.add(2, 3).unwrap(), 5);
+    assert_eq!(client.add(-7, 4).unwrap(), -3);
+
+    let response = client
+        .request(client_message::Message::HealthCheckRequest(
+            HealthCheckRequest {},
+        ))
+        .expect("Health check failed");
+    match response {
+        server_message::Message::HealthCheckResponse(health) => {
+            assert_eq!(health.status(), ServingStatus::Serving)
+        }
+        other => panic!("Expected a HealthCheckResponse, got {:?}", other),
+    }
+
+    client.disconnect().expect("Failed to disconnect");
+    assert!(!client.is_connected());
+    server.stop();
+    handle.join().expect("Server thread panicked");
+}
+
+#[test]
+fn test_errors() {
+    let server = Server::new("localhost:0")
+        .expect("Failed to start")
+        .with_validation(ValidationRules {
+            add_operand_range: Some(0..=10),
+            ..ValidationRules::default()
+        });
+    let (server, handle) = common::start(server);
+    let mut client = Client::new(address(&server));
+    client.connect().expect("Failed to connect");
+
+    match client.add(1, 11) {
+        Err(Error::Server { code, field, .. }) => {
+            assert_eq!(code, ErrorCode::InvalidArgument);
+            assert_eq!(field, "add_request.b");
+        }
+        other => panic!("Expected a server error, got {:?}", other),
+    }
+    // The connection is still usable
+    assert_eq!(client.add(1, 2).unwrap(), 3);
+
+    // Kicked by the server
+    let id = server.connections().remove(0).id;
+    server.disconnect(id).expect("Failed to kick the client");
+    assert!(matches!(
+        client.echo("anyone there?"),
+        Err(Error::Disconnected)
+    ));
+    assert!(!client.is_connected());
+
+    server.stop();
+    handle.join().expect("Server thread panicked");
+    drop(server);
+    assert!(matches!(client.connect(), Err(Error::Io(_))));
+}
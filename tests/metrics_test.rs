@@ -0,0 +1,157 @@
+use embedded_recruitment_task::{
+    message::{client_message, server_message, AddRequest, EchoMessage},
+    server::Server,
+};
+use std::{
+    io::Write,
+    net::{SocketAddr, TcpStream},
+    thread,
+    time::Duration,
+};
+
+mod client;
+mod common;
+
+fn create_server() -> Server {
+    Server::new("localhost:0").expect("Failed to start server")
+}
+
+fn connect(server: &Server) -> client::Client {
+    let addr: SocketAddr = server.address().parse().unwrap();
+    let mut client = client::Client::new(&addr.ip().to_string(), addr.port().into(), 1000);
+    client.connect().expect("Failed to connect to the server");
+    client
+}
+
+#[test]
+fn test_metrics_snapshot() {
+    let (server, handle) = common::start(create_server());
+
+    let mut client = connect(&server);
+    for content in ["one", "two"] {
+        client
+            .send(client_message::Message::EchoMessage(EchoMessage {
+                content: content.to_string(),
+            }))
+            .expect("Failed to send message");
+        assert!(matches!(
+            client.receive().expect("Failed to receive").message,
+            Some(server_message::Message::EchoMessage(_))
+        ));
+    }
+    client
+        .send(client_message::Message::AddRequest(AddRequest {
+            a: 1,
+            b: 2,
+        }))
+        .expect("Failed to send message");
+    assert!(matches!(
+        client.receive().expect("Failed to receive").message,
+        Some(server_message::Message::AddResponse(_))
+    ));
+
+    let metrics = server.metrics();
+    assert_eq!(metrics.connections_accepted, 1);
+    assert_eq!(metrics.connections_active, 1);
+    assert_eq!(metrics.requests.get("echo_message"), Some(&2));
+    assert_eq!(metrics.requests.get("add_request"), Some(&1));
+    assert_eq!(metrics.handler_latency.count, 3);
+    assert_eq!(
+        metrics
+            .handler_latency
+            .buckets
+            .last()
+            .map(|(_, count)| *count),
+        Some(3)
+    );
+    assert!(metrics.bytes_received > 0);
+    assert!(metrics.bytes_sent > 0);
+
+    // Bytes that are not a protobuf message
+    let mut raw = TcpStream::connect(server.address()).unwrap();
+    raw.write_all(&[0xff, 0xff, 0xff]).unwrap();
+    drop(raw);
+    client.disconnect().expect("Failed to disconnect");
+    thread::sleep(Duration::from_millis(200));
+
+    let metrics = server.metrics();
+    assert_eq!(metrics.connections_accepted, 2);
+    assert_eq!(metrics.connections_active, 0);
+    assert_eq!(metrics.connections_closed, 2);
+    assert_eq!(metrics.decode_errors, 1);
+
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_prometheus_format() {
+    let (server, handle) = common::start(create_server());
+
+    let mut client = connect(&server);
+    client
+        .send(client_message::Message::AddRequest(AddRequest {
+            a: 1,
+            b: 2,
+        }))
+        .expect("Failed to send message");
+    client.receive().expect("Failed to receive");
+
+    let text = server.metrics().to_prometheus();
+    assert!(text.contains("# TYPE server_connections_accepted_total counter\n"));
+    assert!(text.contains("server_connections_active 1\n"));
+    assert!(text.contains("server_requests_total{type=\"add_request\"} 1\n"));
+    assert!(text.contains("# TYPE server_handler_duration_seconds histogram\n"));
+    assert!(text.contains("server_handler_duration_seconds_bucket{le=\"+Inf\"} 1\n"));
+    assert!(text.contains("server_handler_duration_seconds_count 1\n"));
+
+    client.disconnect().expect("Failed to disconnect");
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn test_metrics_exporter() {
+    use embedded_recruitment_task::metrics::MetricsExporter;
+    use std::io::Read;
+
+    let (server, handle) = common::start(create_server());
+    let (exporter, exporter_handle) = common::start(
+        MetricsExporter::new("127.0.0.1:0", server.metrics_registry())
+            .expect("Failed to start exporter"),
+    );
+
+    let client = connect(&server);
+    thread::sleep(Duration::from_millis(100));
+
+    let scrape = |path: &str| {
+        let mut stream = TcpStream::connect(exporter.address()).unwrap();
+        write!(
+            stream,
+            "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+            path
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    };
+    let response = scrape("/metrics");
+    assert!(response.starts_with("HTTP/1.1 200"));
+    assert!(response.contains("text/plain; version=0.0.4"));
+    assert!(response.contains("server_connections_accepted_total 1\n"));
+    assert!(scrape("/other").starts_with("HTTP/1.1 404"));
+
+    drop(client);
+    exporter.stop();
+    server.stop();
+    assert!(exporter_handle.join().is_ok());
+    assert!(handle.join().is_ok());
+}
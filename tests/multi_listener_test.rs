@@ -0,0 +1,120 @@
+use embedded_recruitment_task::{
+    listener::Listener,
+    message::{client_message, server_message, AddRequest, ClientMessage, ServerMessage},
+    server::Server,
+};
+use prost::Message;
+use std::{
+    io::{Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+};
+
+mod common;
+
+fn ipv6_available() -> bool {
+    TcpListener::bind("[::1]:0").is_ok()
+}
+
+fn add<S: Read + Write>(stream: &mut S, a: i32, b: i32) -> i32 {
+    let request = ClientMessage {
+        message: Some(client_message::Message::AddRequest(AddRequest { a, b })),
+        ..Default::default()
+    };
+    stream
+        .write_all(&request.encode_to_vec())
+        .expect("Failed to send message");
+
+    let mut buffer = vec![0u8; 65536];
+    let bytes_read = stream.read(&mut buffer).expect("Failed to read response");
+    match ServerMessage::decode(&buffer[..bytes_read])
+        .expect("Failed to decode response")
+        .message
+    {
+        Some(server_message::Message::AddResponse(response)) => response.result,
+        _ => panic!("Expected AddResponse, but received a different message"),
+    }
+}
+
+#[test]
+fn test_server_on_several_listeners() {
+    let mut listeners = vec![
+        Listener::bind_tcp("127.0.0.1:0").unwrap(),
+        Listener::bind_tcp("127.0.0.1:0").unwrap(),
+    ];
+    if ipv6_available() {
+        listeners.push(Listener::bind_tcp("[::1]:0").unwrap());
+    }
+    #[cfg(unix)]
+    let socket_path = std::env::temp_dir().join(format!("ert-multi-{}.sock", std::process::id()));
+    #[cfg(unix)]
+    listeners.push(Listener::bind_unix(&socket_path, &Default::default()).unwrap());
+    let listener_count = listeners.len();
+
+    let (server, handle) =
+        common::start(Server::from_listeners(listeners).expect("Failed to start server"));
+    assert_eq!(server.addresses().len(), listener_count);
+    assert_eq!(server.address(), server.addresses()[0]);
+
+    let mut checked = 0;
+    for address in server.addresses() {
+        if let Some(path) = address.strip_prefix("unix:") {
+            #[cfg(unix)]
+            {
+                let mut stream = std::os::unix::net::UnixStream::connect(path).unwrap();
+                assert_eq!(add(&mut stream, 1, 2), 3);
+                checked += 1;
+            }
+            #[cfg(not(unix))]
+            let _ = path;
+        } else {
+            let addr: SocketAddr = address.parse().expect("Address is not a socket address");
+            let mut stream = TcpStream::connect(addr).unwrap();
+            assert_eq!(add(&mut stream, 20, 22), 42);
+            checked += 1;
+        }
+    }
+    assert_eq!(checked, listener_count);
+
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_server_needs_a_listener() {
+    assert!(Server::from_listeners(Vec::new()).is_err());
+}
+
+#[test]
+fn test_dual_stack_listener() {
+    if !ipv6_available() {
+        eprintln!("IPv6 is not available, skipping");
+        return;
+    }
+
+    let (server, handle) = common::start(
+        Server::from_listeners(vec![Listener::bind_dual_stack(0).unwrap()])
+            .expect("Failed to start server"),
+    );
+    let port = server
+        .address()
+        .parse::<SocketAddr>()
+        .expect("Address is not a socket address")
+        .port();
+
+    // The same port serves both address families
+    let mut v4 = TcpStream::connect(("127.0.0.1", port)).expect("IPv4 connect failed");
+    assert_eq!(add(&mut v4, 2, 2), 4);
+    let mut v6 = TcpStream::connect(("::1", port)).expect("IPv6 connect failed");
+    assert_eq!(add(&mut v6, 3, 3), 6);
+
+    drop(v4);
+    drop(v6);
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
@@ -0,0 +1,197 @@
+use embedded_recruitment_task::{
+    auth::CredentialStore,
+    handler::{self, ClientMessageWrapper, REQUEST_TYPES},
+    message::{
+        auth_request::Credential, client_message, server_message, AddRequest, AuthChallengeRequest,
+        AuthRequest, ClientMessage, EchoMessage, ErrorCode, HealthCheckRequest, ServerMessage,
+        StatsRequest,
+    },
+    policy::Policy,
+    server::Server,
+};
+use std::{net::SocketAddr, sync::Arc, thread::JoinHandle};
+
+mod client;
+mod common;
+
+fn start_server() -> (Arc<Server>, JoinHandle<()>) {
+    let store = CredentialStore::new()
+        .with_token("ops", "ops-token")
+        .with_token("dashboard", "dashboard-token")
+        .with_token("guest", "guest-token");
+    let policy = Policy::new()
+        .with_role("monitor", &["echo_message", "stats_request"])
+        .and_then(|policy| policy.with_role("operator", &["*"]))
+        .map(|policy| {
+            policy
+                .with_assignment("dashboard", &["monitor"])
+                .with_assignment("ops", &["operator"])
+        })
+        .expect("Invalid policy");
+    common::start(
+        Server::new("localhost:0")
+            .expect("Failed to start server")
+            .with_credentials(store)
+            .with_policy(policy),
+    )
+}
+
+fn connect_as(server: &Server, token: &str) -> client::Client {
+    let addr: SocketAddr = server.address().parse().unwrap();
+    let mut client = client::Client::new(&addr.ip().to_string(), addr.port().into(), 1000);
+    client.connect().expect("Failed to connect to the server");
+    let response = request(
+        &mut client,
+        client_message::Message::AuthRequest(AuthRequest {
+            identity: String::new(),
+            credential: Some(Credential::Token(token.to_string())),
+        }),
+    );
+    assert!(matches!(
+        response.message,
+        Some(server_message::Message::AuthResponse(_))
+    ));
+    client
+}
+
+fn request(client: &mut client::Client, message: client_message::Message) -> ServerMessage {
+    client.send(message).expect("Failed to send message");
+    client.receive().expect("Failed to receive")
+}
+
+fn add(client: &mut client::Client) -> ServerMessage {
+    request(
+        client,
+        client_message::Message::AddRequest(AddRequest { a: 1, b: 2 }),
+    )
+}
+
+fn echo(client: &mut client::Client) -> ServerMessage {
+    request(
+        client,
+        client_message::Message::EchoMessage(EchoMessage {
+            content: "hello".to_string(),
+        }),
+    )
+}
+
+fn denied(response: &ServerMessage) -> bool {
+    matches!(
+        &response.message,
+        Some(server_message::Message::Error(error)) if error.code() == ErrorCode::PermissionDenied
+    )
+}
+
+#[test]
+fn test_policy_enforced_per_identity() {
+    let (server, handle) = start_server();
+
+    let mut dashboard = connect_as(&server, "dashboard-token");
+    assert!(!denied(&echo(&mut dashboard)));
+    assert!(matches!(
+        request(
+            &mut dashboard,
+            client_message::Message::StatsRequest(StatsRequest {})
+        )
+        .message,
+        Some(server_message::Message::StatsResponse(_))
+    ));
+    let response = add(&mut dashboard);
+    assert!(denied(&response));
+    match response.message {
+        Some(server_message::Message::Error(error)) => {
+            assert_eq!(error.message, "dashboard may not send add_request")
+        }
+        _ => unreachable!(),
+    }
+
+    let mut ops = connect_as(&server, "ops-token");
+    assert!(matches!(
+        add(&mut ops).message,
+        Some(server_message::Message::AddResponse(response)) if response.result == 3
+    ));
+
+    // Authenticated, but not in the policy
+    let mut guest = connect_as(&server, "guest-token");
+    assert!(denied(&echo(&mut guest)));
+    assert!(matches!(
+        request(
+            &mut guest,
+            client_message::Message::HealthCheckRequest(HealthCheckRequest {})
+        )
+        .message,
+        Some(server_message::Message::HealthCheckResponse(_))
+    ));
+
+    for mut client in [dashboard, ops, guest] {
+        client.disconnect().expect("Failed to disconnect");
+    }
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_policy_file() {
+    let path = std::env::temp_dir().join(format!("ert-policy-{}", std::process::id()));
+    std::fs::write(
+        &path,
+        "# read-only monitoring\nrole monitor echo_message stats_request\n\
+         assign dashboard monitor\nallow ops add_request\n",
+    )
+    .unwrap();
+    let policy = Policy::from_file(&path).expect("Failed to load policy");
+    assert!(policy.allows(Some("dashboard"), "stats_request"));
+    assert!(!policy.allows(Some("dashboard"), "add_request"));
+    assert!(policy.allows(Some("ops"), "add_request"));
+    assert!(!policy.allows(Some("ops"), "echo_message"));
+    assert!(!policy.allows(None, "echo_message"));
+    assert!(policy.allows(None, "auth_request"));
+
+    std::fs::write(&path, "role monitor reboot\n").unwrap();
+    let error = Policy::from_file(&path).expect_err("Expected an error");
+    assert_eq!(error.to_string(), "line 1: unknown message type reboot");
+
+    std::fs::write(&path, "assign dashboard monitor\n").unwrap();
+    let error = Policy::from_file(&path).expect_err("Expected an error");
+    assert!(error.to_string().contains("unknown role monitor"));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_request_types_cover_every_message() {
+    use client_message::Message;
+
+    let messages = [
+        Message::EchoMessage(EchoMessage::default()),
+        Message::AddRequest(AddRequest::default()),
+        Message::HealthCheckRequest(HealthCheckRequest::default()),
+        Message::StatsRequest(StatsRequest::default()),
+        Message::AuthChallengeRequest(AuthChallengeRequest::default()),
+        Message::AuthRequest(AuthRequest::default()),
+    ];
+    // Stops compiling when a message type is added, until it is listed above
+    for message in &messages {
+        match message {
+            Message::EchoMessage(_)
+            | Message::AddRequest(_)
+            | Message::HealthCheckRequest(_)
+            | Message::StatsRequest(_)
+            | Message::AuthChallengeRequest(_)
+            | Message::AuthRequest(_) => {}
+        }
+    }
+
+    let names: Vec<_> = messages
+        .into_iter()
+        .map(|message| {
+            handler::request_type(&ClientMessageWrapper::from(ClientMessage {
+                message: Some(message),
+                ..Default::default()
+            }))
+        })
+        .collect();
+    assert_eq!(names, REQUEST_TYPES);
+}
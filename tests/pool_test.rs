@@ -0,0 +1,214 @@
+use embedded_recruitment_task::{
+    auth::CredentialStore,
+    client::{Client, Error},
+    message::{auth_request::Credential, client_message, AuthRequest, ErrorCode},
+    pool::{Pool, PoolConfig},
+    server::Server,
+};
+use std::{
+    net::SocketAddr,
+    sync::Arc,
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+mod common;
+
+fn pool(server: &Server, config: PoolConfig) -> Pool {
+    let addr: SocketAddr = server.address().parse().unwrap();
+    Pool::new(config, move || Client::new(addr)).expect("Failed to create pool")
+}
+
+fn stop(server: Arc<Server>, handle: JoinHandle<()>) {
+    for connection in server.connections() {
+        server.disconnect(connection.id).expect("Failed to kick");
+    }
+    server.stop();
+    handle.join().expect("Server thread panicked");
+}
+
+#[test]
+fn test_concurrent_callers_share_connections() {
+    let (server, handle) =
+        common::start(Server::new("localhost:0").expect("Failed to start server"));
+    let pool = pool(
+        &server,
+        PoolConfig {
+            min_size: 1,
+            max_size: 3,
+            ..PoolConfig::default()
+        },
+    );
+    assert_eq!(pool.size(), 1);
+
+    let workers: Vec<_> = (0..8)
+        .map(|i| {
+            let pool = pool.clone();
+            thread::spawn(move || {
+                for j in 0..20 {
+                    let mut client = pool.get().expect("Failed to check out");
+                    assert_eq!(client.add(i, j).unwrap(), i + j);
+                }
+            })
+        })
+        .collect();
+    for worker in workers {
+        worker.join().expect("Worker panicked");
+    }
+
+    assert!(pool.size() <= 3, "{} connections open", pool.size());
+    assert_eq!(pool.idle(), pool.size());
+    assert_eq!(server.connections().len(), pool.size());
+
+    drop(pool);
+    stop(server, handle);
+}
+
+#[test]
+fn test_checkout_timeout() {
+    let (server, handle) =
+        common::start(Server::new("localhost:0").expect("Failed to start server"));
+    let pool = pool(
+        &server,
+        PoolConfig {
+            min_size: 0,
+            max_size: 1,
+            checkout_timeout: Duration::from_millis(200),
+            ..PoolConfig::default()
+        },
+    );
+    assert_eq!(pool.size(), 0);
+
+    let mut held = pool.get().expect("Failed to check out");
+    let start = Instant::now();
+    assert!(matches!(pool.get(), Err(Error::CheckoutTimeout)));
+    assert!(start.elapsed() >= Duration::from_millis(200));
+
+    // A returned connection is handed to a waiting caller
+    let waiter = {
+        let pool = pool.clone();
+        thread::spawn(move || pool.get().map(|mut client| client.echo("next").unwrap()))
+    };
+    thread::sleep(Duration::from_millis(50));
+    assert_eq!(held.echo("first").unwrap(), "first");
+    drop(held);
+    assert_eq!(waiter.join().unwrap().expect("Waiter timed out"), "next");
+    assert_eq!(pool.size(), 1);
+
+    drop(pool);
+    stop(server, handle);
+}
+
+#[test]
+fn test_health_check_and_idle_eviction() {
+    let (server, handle) =
+        common::start(Server::new("localhost:0").expect("Failed to start server"));
+    let pool = pool(
+        &server,
+        PoolConfig {
+            min_size: 1,
+            max_size: 3,
+            idle_timeout: Duration::from_millis(100),
+            health_check_after: Duration::ZERO,
+            ..PoolConfig::default()
+        },
+    );
+
+    // Lost connections are replaced instead of handed out
+    for connection in server.connections() {
+        server.disconnect(connection.id).expect("Failed to kick");
+    }
+    thread::sleep(Duration::from_millis(50));
+    assert_eq!(pool.get().unwrap().echo("fresh").unwrap(), "fresh");
+    assert_eq!(pool.size(), 1);
+
+    // Surplus connections are closed once idle for long enough
+    let clients: Vec<_> = (0..3).map(|_| pool.get().unwrap()).collect();
+    drop(clients);
+    assert_eq!(pool.size(), 3);
+    thread::sleep(Duration::from_millis(150));
+    assert_eq!(pool.get().unwrap().add(1, 1).unwrap(), 2);
+    assert_eq!(pool.size(), 1);
+    thread::sleep(Duration::from_millis(50));
+    assert_eq!(server.connections().len(), 1);
+
+    drop(pool);
+    stop(server, handle);
+}
+
+#[test]
+fn test_invalid_config() {
+    let addr: SocketAddr = "127.0.0.1:1".parse().unwrap();
+    let config = PoolConfig {
+        min_size: 4,
+        max_size: 2,
+        ..PoolConfig::default()
+    };
+    assert!(matches!(
+        Pool::new(config, move || Client::new(addr)),
+        Err(Error::Io(_))
+    ));
+}
+
+fn authenticate(client: &mut Client, token: &str) -> Result<(), Error> {
+    client.request(client_message::Message::AuthRequest(AuthRequest {
+        identity: String::new(),
+        credential: Some(Credential::Token(token.to_string())),
+    }))?;
+    Ok(())
+}
+
+#[test]
+fn test_authenticated_connections() {
+    let (server, handle) = common::start(
+        Server::new("localhost:0")
+            .expect("Failed to start server")
+            .with_credentials(CredentialStore::new().with_token("device-1", "device-token")),
+    );
+    let addr: SocketAddr = server.address().parse().unwrap();
+    let config = PoolConfig {
+        min_size: 1,
+        max_size: 2,
+        ..PoolConfig::default()
+    };
+
+    let pool = Pool::with_on_connect(
+        config.clone(),
+        move || Client::new(addr),
+        |client| authenticate(client, "device-token"),
+    )
+    .expect("Failed to create pool");
+    // Both the connection opened up front and one opened on demand
+    let mut first = pool.get().unwrap();
+    let mut second = pool.get().unwrap();
+    assert_eq!(first.add(1, 2).unwrap(), 3);
+    assert_eq!(second.add(3, 4).unwrap(), 7);
+    drop((first, second));
+    assert_eq!(pool.size(), 2);
+
+    // Without the callback the server refuses to serve the connections
+    let bare = Pool::new(config.clone(), move || Client::new(addr)).unwrap();
+    assert!(matches!(
+        bare.get().unwrap().add(1, 2),
+        Err(Error::Server {
+            code: ErrorCode::Unauthenticated,
+            ..
+        })
+    ));
+
+    // A failing callback fails the pool's connects
+    assert!(matches!(
+        Pool::with_on_connect(
+            config,
+            move || Client::new(addr),
+            |client| authenticate(client, "wrong-token"),
+        ),
+        Err(Error::Server {
+            code: ErrorCode::Unauthenticated,
+            ..
+        })
+    ));
+
+    drop((pool, bare));
+    stop(server, handle);
+}
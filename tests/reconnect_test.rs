@@ -0,0 +1,240 @@
+use embedded_recruitment_task::{
+    auth::CredentialStore,
+    client::{Client, ConnectionState, Error, ReconnectPolicy},
+    message::{
+        auth_request::Credential, client_message, AuthChallengeRequest, AuthRequest, ErrorCode,
+    },
+    server::Server,
+};
+use std::{
+    net::{SocketAddr, TcpListener},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+mod common;
+
+fn policy(max_retries: u32) -> ReconnectPolicy {
+    ReconnectPolicy {
+        initial_backoff: Duration::from_millis(10),
+        max_backoff: Duration::from_millis(50),
+        max_retries: Some(max_retries),
+        ..ReconnectPolicy::default()
+    }
+}
+
+/// Client that records its state changes
+fn client(addr: SocketAddr, policy: ReconnectPolicy) -> (Client, Arc<Mutex<Vec<ConnectionState>>>) {
+    let states = Arc::new(Mutex::new(Vec::new()));
+    let client = {
+        let states = states.clone();
+        Client::new(addr)
+            .with_reconnect(policy)
+            .on_state_change(move |state| states.lock().unwrap().push(state))
+    };
+    (client, states)
+}
+
+fn kick_all(server: &Server) {
+    for connection in server.connections() {
+        server.disconnect(connection.id).expect("Failed to kick");
+    }
+    thread::sleep(Duration::from_millis(50));
+}
+
+#[test]
+fn test_reconnects_and_retries_idempotent_requests() {
+    let (server, handle) =
+        common::start(Server::new("localhost:0").expect("Failed to start server"));
+    let (mut client, states) = client(server.address().parse().unwrap(), policy(3));
+
+    // The first request connects
+    assert_eq!(client.echo("first").unwrap(), "first");
+    kick_all(&server);
+    assert_eq!(client.add(2, 2).unwrap(), 4);
+    assert_eq!(server.connections().len(), 1);
+
+    // Authentication is not retried on a new connection
+    kick_all(&server);
+    let challenge = client_message::Message::AuthChallengeRequest(AuthChallengeRequest::default());
+    assert!(matches!(
+        client.request(challenge),
+        Err(Error::Disconnected)
+    ));
+    assert!(!client.is_connected());
+    assert_eq!(client.echo("again").unwrap(), "again");
+
+    assert_eq!(
+        *states.lock().unwrap(),
+        [
+            ConnectionState::Reconnecting { attempt: 1 },
+            ConnectionState::Connected,
+            ConnectionState::Disconnected,
+            ConnectionState::Reconnecting { attempt: 1 },
+            ConnectionState::Connected,
+            ConnectionState::Disconnected,
+            ConnectionState::Reconnecting { attempt: 1 },
+            ConnectionState::Connected,
+        ]
+    );
+
+    client.disconnect().expect("Failed to disconnect");
+    server.stop();
+    handle.join().expect("Server thread panicked");
+}
+
+#[test]
+fn test_gives_up_after_max_retries() {
+    let (server, handle) =
+        common::start(Server::new("localhost:0").expect("Failed to start server"));
+    let addr = server.address().parse().unwrap();
+    server.stop();
+    handle.join().expect("Server thread panicked");
+    drop(server);
+
+    let (mut client, states) = client(addr, policy(2));
+    assert!(matches!(client.echo("hello"), Err(Error::Io(_))));
+    assert_eq!(
+        *states.lock().unwrap(),
+        [
+            ConnectionState::Reconnecting { attempt: 1 },
+            ConnectionState::Reconnecting { attempt: 2 },
+            ConnectionState::Reconnecting { attempt: 3 },
+            ConnectionState::Disconnected,
+        ]
+    );
+}
+
+#[test]
+fn test_backoff() {
+    let policy = ReconnectPolicy {
+        initial_backoff: Duration::from_millis(100),
+        max_backoff: Duration::from_millis(500),
+        multiplier: 2.0,
+        jitter: 0.5,
+        max_retries: None,
+    };
+    for (retry, full) in [(0, 100), (1, 200), (2, 400), (3, 500), (10, 500)] {
+        let full = Duration::from_millis(full);
+        for _ in 0..20 {
+            let delay = policy.backoff(retry);
+            assert!(
+                delay <= full && delay >= full / 2,
+                "Retry {} waits {:?}",
+                retry,
+                delay
+            );
+        }
+    }
+
+    let exact = ReconnectPolicy {
+        jitter: 0.0,
+        ..policy.clone()
+    };
+    assert_eq!(exact.backoff(1), Duration::from_millis(200));
+
+    // Nonsensical settings are clamped instead of panicking
+    let clamped = ReconnectPolicy {
+        multiplier: -2.0,
+        jitter: f64::NAN,
+        ..policy.clone()
+    };
+    assert_eq!(clamped.backoff(3), Duration::from_millis(100));
+    let clamped = ReconnectPolicy {
+        initial_backoff: Duration::ZERO,
+        multiplier: f64::INFINITY,
+        jitter: -1.0,
+        ..policy
+    };
+    assert_eq!(clamped.backoff(u32::MAX), Duration::from_millis(500));
+    let unbounded = ReconnectPolicy {
+        max_backoff: Duration::MAX,
+        jitter: 0.0,
+        ..clamped
+    };
+    assert_eq!(unbounded.backoff(100), Duration::MAX);
+}
+
+/// Authenticates each connection with `token`, counting the connections
+fn authenticate(
+    token: &'static str,
+    count: Arc<AtomicU32>,
+) -> impl FnMut(&mut Client) -> Result<(), Error> {
+    move |client| {
+        count.fetch_add(1, Ordering::SeqCst);
+        client.request(client_message::Message::AuthRequest(AuthRequest {
+            identity: String::new(),
+            credential: Some(Credential::Token(token.to_string())),
+        }))?;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_reauthenticates_after_reconnecting() {
+    let store = CredentialStore::new().with_token("device-1", "device-token");
+    let (server, handle) = common::start(
+        Server::new("localhost:0")
+            .expect("Failed to start server")
+            .with_credentials(store),
+    );
+    let addr = server.address().parse().unwrap();
+
+    let connections = Arc::new(AtomicU32::new(0));
+    let mut client = Client::new(addr)
+        .with_reconnect(policy(3))
+        .on_connect(authenticate("device-token", connections.clone()));
+    client.connect().expect("Failed to connect");
+    assert_eq!(client.add(1, 2).unwrap(), 3);
+    kick_all(&server);
+    // Unauthenticated, the server would answer with an error
+    assert_eq!(client.add(2, 3).unwrap(), 5);
+    assert_eq!(connections.load(Ordering::SeqCst), 2);
+
+    // Rejected credentials fail the connect and are not retried
+    let attempts = Arc::new(AtomicU32::new(0));
+    let mut rejected = Client::new(addr)
+        .with_reconnect(policy(3))
+        .on_connect(authenticate("wrong-token", attempts.clone()));
+    assert!(matches!(
+        rejected.connect(),
+        Err(Error::Server {
+            code: ErrorCode::Unauthenticated,
+            ..
+        })
+    ));
+    assert!(!rejected.is_connected());
+    assert!(matches!(rejected.echo("hello"), Err(Error::Server { .. })));
+    assert_eq!(attempts.load(Ordering::SeqCst), 2);
+
+    client.disconnect().expect("Failed to disconnect");
+    kick_all(&server);
+    server.stop();
+    handle.join().expect("Server thread panicked");
+}
+
+#[test]
+fn test_one_retry_budget_per_request() {
+    // Accepts connections and closes them right away
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let accepted = Arc::new(AtomicU32::new(0));
+    {
+        let accepted = accepted.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                accepted.fetch_add(1, Ordering::SeqCst);
+                drop(stream);
+            }
+        });
+    }
+
+    let (mut client, _) = client(addr, policy(2));
+    assert!(matches!(client.echo("hello"), Err(Error::Disconnected)));
+    thread::sleep(Duration::from_millis(50));
+    assert_eq!(accepted.load(Ordering::SeqCst), 3);
+}
@@ -0,0 +1,172 @@
+#![cfg(feature = "record")]
+
+use embedded_recruitment_task::{
+    auth::CredentialStore,
+    framing::Framing,
+    message::{
+        auth_request::Credential, client_message, server_message, AddRequest, AuthRequest,
+        EchoMessage,
+    },
+    recorder::{self, Recorder},
+    server::Server,
+};
+use std::{
+    io::Write,
+    net::{SocketAddr, TcpStream},
+    path::PathBuf,
+    sync::Arc,
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+mod client;
+mod common;
+
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+fn recording_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("ert-{}-{}.jsonl", name, std::process::id()))
+}
+
+fn stop_server(server: Arc<Server>, handle: JoinHandle<()>) {
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+/// Records a session with an echo, an add and an undecodable message
+fn record_session(path: &PathBuf) {
+    let _ = std::fs::remove_file(path);
+    let server = Server::new("127.0.0.1:0")
+        .expect("Failed to start server")
+        .with_recorder(Recorder::create(path).expect("Failed to create recording"));
+    let (server, handle) = common::start(server);
+
+    let addr: SocketAddr = server.address().parse().unwrap();
+    let mut client = client::Client::new(&addr.ip().to_string(), addr.port().into(), 1000);
+    client.connect().expect("Failed to connect to the server");
+    client
+        .send(client_message::Message::EchoMessage(EchoMessage {
+            content: "recorded".to_string(),
+        }))
+        .expect("Failed to send message");
+    client.receive().expect("Failed to receive");
+    client
+        .send(client_message::Message::AddRequest(AddRequest {
+            a: 2,
+            b: 3,
+        }))
+        .expect("Failed to send message");
+    client.receive().expect("Failed to receive");
+    client.disconnect().expect("Failed to disconnect");
+
+    let mut raw = TcpStream::connect(server.address()).unwrap();
+    raw.write_all(&[0xff, 0xff, 0xff]).unwrap();
+    thread::sleep(Duration::from_millis(100));
+    drop(raw);
+
+    stop_server(server, handle);
+}
+
+#[test]
+fn test_record_and_replay() {
+    let path = recording_path("record");
+    record_session(&path);
+
+    let records = recorder::read_recording(&path).expect("Failed to read recording");
+    assert_eq!(records.len(), 3);
+    assert_eq!(records[0].connection, records[1].connection);
+    assert_ne!(records[1].connection, records[2].connection);
+    assert!(matches!(
+        records[1].response.as_ref().and_then(|r| r.message.as_ref()),
+        Some(server_message::Message::AddResponse(response)) if response.result == 5
+    ));
+    // Undecodable input is kept byte for byte
+    assert_eq!(records[2].raw, "ffffff");
+    assert!(records[2].request.is_none() && records[2].response.is_none());
+
+    let (server, handle) = common::start(Server::new("127.0.0.1:0").unwrap());
+    let report = recorder::replay(&records, server.address(), Framing::Unframed, TIMEOUT)
+        .expect("Replay failed");
+    assert_eq!(report.replayed, 3);
+    assert!(report.mismatches.is_empty(), "{:?}", report.mismatches);
+    stop_server(server, handle);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_replay_reports_mismatches() {
+    let path = recording_path("mismatch");
+    record_session(&path);
+    let mut records = recorder::read_recording(&path).unwrap();
+
+    // Pretend the recorded server answered 2 + 3 differently
+    if let Some(server_message::Message::AddResponse(response)) = records[1]
+        .response
+        .as_mut()
+        .and_then(|r| r.message.as_mut())
+    {
+        response.result = 6;
+    }
+
+    let (server, handle) = common::start(Server::new("127.0.0.1:0").unwrap());
+    let report = recorder::replay(&records, server.address(), Framing::Unframed, TIMEOUT)
+        .expect("Replay failed");
+    assert_eq!(report.mismatches.len(), 1);
+    let mismatch = &report.mismatches[0];
+    assert_eq!(mismatch.index, 1);
+    assert!(matches!(
+        mismatch.actual.as_ref().and_then(|r| r.message.as_ref()),
+        Some(server_message::Message::AddResponse(response)) if response.result == 5
+    ));
+    assert!(mismatch.to_string().contains("\"result\":6"));
+    stop_server(server, handle);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_credentials_are_redacted() {
+    let path = recording_path("redact");
+    let _ = std::fs::remove_file(&path);
+    let server = Server::new("127.0.0.1:0")
+        .expect("Failed to start server")
+        .with_credentials(CredentialStore::new().with_token("device-1", "s3cret-t0ken"))
+        .with_recorder(Recorder::create(&path).expect("Failed to create recording"));
+    let (server, handle) = common::start(server);
+
+    let addr: SocketAddr = server.address().parse().unwrap();
+    let mut client = client::Client::new(&addr.ip().to_string(), addr.port().into(), 1000);
+    client.connect().expect("Failed to connect to the server");
+    client
+        .send(client_message::Message::AuthRequest(AuthRequest {
+            identity: String::new(),
+            credential: Some(Credential::Token("s3cret-t0ken".to_string())),
+        }))
+        .expect("Failed to send message");
+    assert!(matches!(
+        client.receive().expect("Failed to receive").message,
+        Some(server_message::Message::AuthResponse(_))
+    ));
+    client.disconnect().expect("Failed to disconnect");
+    stop_server(server, handle);
+
+    let recording = std::fs::read_to_string(&path).unwrap();
+    let token_hex: String = "s3cret-t0ken"
+        .bytes()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    assert!(!recording.contains("s3cret-t0ken"), "{}", recording);
+    assert!(!recording.contains(&token_hex), "{}", recording);
+
+    let records = recorder::read_recording(&path).unwrap();
+    assert!(matches!(
+        records[0].request.as_ref().and_then(|r| r.message.as_ref()),
+        Some(client_message::Message::AuthRequest(AuthRequest {
+            credential: Some(Credential::Token(token)),
+            ..
+        })) if token == recorder::REDACTED
+    ));
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,56 @@
+#![cfg(unix)]
+
+use embedded_recruitment_task::{
+    message::{client_message, server_message, AddRequest},
+    server::Server,
+};
+use std::{net::SocketAddr, thread};
+
+mod client;
+mod common;
+
+#[test]
+fn test_reuseport_acceptors_share_a_port() {
+    let (server, handle) =
+        common::start(Server::new_reuseport("127.0.0.1:0", 4).expect("Failed to start server"));
+    assert_eq!(server.addresses().len(), 4);
+    assert!(server.addresses().iter().all(|a| a == server.address()));
+
+    let addr: SocketAddr = server.address().parse().unwrap();
+    let clients: Vec<_> = (0..20)
+        .map(|i| {
+            thread::spawn(move || {
+                let mut client =
+                    client::Client::new(&addr.ip().to_string(), addr.port().into(), 1000);
+                client.connect().expect("Failed to connect to the server");
+                client
+                    .send(client_message::Message::AddRequest(AddRequest {
+                        a: i,
+                        b: 1,
+                    }))
+                    .expect("Failed to send AddRequest");
+                match client.receive().expect("Failed to receive").message {
+                    Some(server_message::Message::AddResponse(response)) => {
+                        assert_eq!(response.result, i + 1)
+                    }
+                    _ => panic!("Expected AddResponse, but received a different message"),
+                }
+                client.disconnect().expect("Failed to disconnect");
+            })
+        })
+        .collect();
+    for client in clients {
+        client.join().expect("Client thread panicked");
+    }
+
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_reuseport_needs_an_acceptor() {
+    assert!(Server::new_reuseport("127.0.0.1:0", 0).is_err());
+}
@@ -0,0 +1,130 @@
+use embedded_recruitment_task::{
+    listener::{KeepaliveOptions, Listener, ListenerOptions, Stream, StreamOptions},
+    message::{client_message, server_message, EchoMessage},
+    server::Server,
+};
+use socket2::SockRef;
+use std::{
+    io::ErrorKind,
+    net::{SocketAddr, TcpStream},
+    time::Duration,
+};
+
+mod client;
+mod common;
+
+fn local_port(listener: &Listener) -> u16 {
+    listener
+        .local_addr()
+        .unwrap()
+        .parse::<SocketAddr>()
+        .unwrap()
+        .port()
+}
+
+#[test]
+fn test_stream_options_are_applied() {
+    let listener = Listener::bind_tcp("127.0.0.1:0").unwrap();
+    let options = StreamOptions {
+        nodelay: Some(true),
+        keepalive: Some(KeepaliveOptions {
+            time: Duration::from_secs(30),
+            interval: Some(Duration::from_secs(5)),
+            retries: Some(4),
+        }),
+        send_buffer_size: Some(64 * 1024),
+        recv_buffer_size: Some(64 * 1024),
+    };
+
+    let _client = TcpStream::connect(("127.0.0.1", local_port(&listener))).unwrap();
+    let (stream, _) = listener.accept_with(&options).expect("Failed to accept");
+    let Stream::Tcp(stream) = stream else {
+        panic!("Expected a plain TCP stream");
+    };
+
+    let socket = SockRef::from(&stream);
+    assert!(socket.nodelay().unwrap());
+    assert!(socket.keepalive().unwrap());
+    #[cfg(target_os = "linux")]
+    {
+        assert_eq!(socket.keepalive_time().unwrap(), Duration::from_secs(30));
+        assert_eq!(socket.keepalive_interval().unwrap(), Duration::from_secs(5));
+        assert_eq!(socket.keepalive_retries().unwrap(), 4);
+    }
+    // The kernel may round buffer sizes up (Linux doubles them)
+    assert!(socket.send_buffer_size().unwrap() >= 64 * 1024);
+    assert!(socket.recv_buffer_size().unwrap() >= 64 * 1024);
+}
+
+#[test]
+fn test_default_stream_options_keep_system_defaults() {
+    let listener = Listener::bind_tcp("127.0.0.1:0").unwrap();
+    let _client = TcpStream::connect(("127.0.0.1", local_port(&listener))).unwrap();
+    let (stream, _) = listener.accept().expect("Failed to accept");
+    let Stream::Tcp(stream) = stream else {
+        panic!("Expected a plain TCP stream");
+    };
+
+    let socket = SockRef::from(&stream);
+    assert!(!socket.nodelay().unwrap());
+    assert!(!socket.keepalive().unwrap());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_reuse_port() {
+    let options = ListenerOptions {
+        reuse_port: true,
+        ..Default::default()
+    };
+    let first = Listener::bind_tcp_with("127.0.0.1:0", &options).unwrap();
+    let port = local_port(&first);
+
+    // A second socket may share the port only if both ask for SO_REUSEPORT
+    let addr = format!("127.0.0.1:{}", port);
+    assert!(Listener::bind_tcp_with(&addr, &options).is_ok());
+    let err = Listener::bind_tcp_with(&addr, &ListenerOptions::default())
+        .err()
+        .expect("Binding without SO_REUSEPORT should fail");
+    assert_eq!(err.kind(), ErrorKind::AddrInUse);
+}
+
+#[test]
+fn test_server_with_socket_options() {
+    let options = ListenerOptions {
+        backlog: 16,
+        ..Default::default()
+    };
+    let listener = Listener::bind_tcp_with("localhost:0", &options).unwrap();
+    let server = Server::from_listeners(vec![listener])
+        .expect("Failed to start server")
+        .with_stream_options(StreamOptions {
+            nodelay: Some(true),
+            ..Default::default()
+        });
+    let (server, handle) = common::start(server);
+
+    let addr: SocketAddr = server.address().parse().unwrap();
+    let mut client = client::Client::new(&addr.ip().to_string(), addr.port().into(), 1000);
+    client.connect().expect("Failed to connect to the server");
+
+    let echo = EchoMessage {
+        content: "low latency".to_string(),
+    };
+    client
+        .send(client_message::Message::EchoMessage(echo.clone()))
+        .expect("Failed to send message");
+    match client.receive().expect("Failed to receive").message {
+        Some(server_message::Message::EchoMessage(response)) => {
+            assert_eq!(response.content, echo.content)
+        }
+        _ => panic!("Expected EchoMessage, but received a different message"),
+    }
+
+    client.disconnect().expect("Failed to disconnect");
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
@@ -0,0 +1,92 @@
+use embedded_recruitment_task::{
+    message::{
+        client_message, server_message, AddRequest, EchoMessage, StatsRequest, StatsResponse,
+    },
+    server::Server,
+};
+use std::{
+    io::Write,
+    net::{SocketAddr, TcpStream},
+    thread,
+    time::Duration,
+};
+
+mod client;
+mod common;
+
+fn create_server() -> Server {
+    Server::new("localhost:0").expect("Failed to start server")
+}
+
+fn connect(server: &Server) -> client::Client {
+    let addr: SocketAddr = server.address().parse().unwrap();
+    let mut client = client::Client::new(&addr.ip().to_string(), addr.port().into(), 1000);
+    client.connect().expect("Failed to connect to the server");
+    client
+}
+
+fn stats(client: &mut client::Client) -> StatsResponse {
+    client
+        .send(client_message::Message::StatsRequest(StatsRequest {}))
+        .expect("Failed to send message");
+    match client.receive().expect("Failed to receive").message {
+        Some(server_message::Message::StatsResponse(response)) => response,
+        _ => panic!("Expected StatsResponse, but received a different message"),
+    }
+}
+
+#[test]
+fn test_stats_request() {
+    let (server, handle) = common::start(create_server());
+
+    let mut first = connect(&server);
+    let mut second = connect(&server);
+    first
+        .send(client_message::Message::EchoMessage(EchoMessage {
+            content: "stats".to_string(),
+        }))
+        .expect("Failed to send message");
+    first.receive().expect("Failed to receive");
+    for (a, b) in [(1, 2), (3, 4)] {
+        first
+            .send(client_message::Message::AddRequest(AddRequest { a, b }))
+            .expect("Failed to send message");
+        first.receive().expect("Failed to receive");
+    }
+
+    // Bytes that are not a protobuf message
+    let mut raw = TcpStream::connect(server.address()).unwrap();
+    raw.write_all(&[0xff, 0xff, 0xff]).unwrap();
+    drop(raw);
+    thread::sleep(Duration::from_millis(200));
+
+    let response = stats(&mut first);
+    assert!(response.uptime_ms >= 300);
+    assert_eq!(response.active_connections, 2);
+    assert_eq!(response.connections_accepted, 3);
+    assert_eq!(response.requests.get("echo_message"), Some(&1));
+    assert_eq!(response.requests.get("add_request"), Some(&2));
+    assert_eq!(response.decode_errors, 1);
+    assert!(response.bytes_received > 0 && response.bytes_sent > 0);
+
+    // The connection's own counters do not include the stats request itself
+    let connection = response.connection.expect("Missing connection counters");
+    assert_eq!(connection.id, server.connections()[0].id);
+    assert_eq!(connection.requests, 3);
+    assert_eq!(connection.decode_errors, 0);
+
+    let response = stats(&mut second);
+    assert_eq!(response.requests.get("stats_request"), Some(&1));
+    let connection = response.connection.expect("Missing connection counters");
+    assert_eq!(connection.id, server.connections()[1].id);
+    assert_eq!(connection.requests, 0);
+    assert_eq!(connection.bytes_sent, 0);
+
+    first.disconnect().expect("Failed to disconnect");
+    second.disconnect().expect("Failed to disconnect");
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
@@ -0,0 +1,148 @@
+#![cfg(unix)]
+
+use embedded_recruitment_task::{
+    listener::Listener,
+    message::{client_message, server_message, AddRequest, ClientMessage, ServerMessage},
+    server::Server,
+    systemd,
+};
+use prost::Message;
+use std::{
+    env,
+    io::{ErrorKind, Read, Write},
+    net::{TcpListener, TcpStream},
+    os::unix::{
+        io::{AsRawFd, OwnedFd},
+        net::UnixListener,
+        process::CommandExt,
+    },
+    process::{Command, Stdio},
+    thread,
+    time::Duration,
+};
+
+mod common;
+
+/// Set in the environment of the socket activated child process
+const CHILD_ENV: &str = "ERT_SYSTEMD_CHILD";
+
+fn add(stream: &mut TcpStream, a: i32, b: i32) -> i32 {
+    let request = ClientMessage {
+        message: Some(client_message::Message::AddRequest(AddRequest { a, b })),
+        ..Default::default()
+    };
+    stream
+        .write_all(&request.encode_to_vec())
+        .expect("Failed to send message");
+
+    let mut buffer = vec![0u8; 65536];
+    let bytes_read = stream.read(&mut buffer).expect("Failed to read response");
+    match ServerMessage::decode(&buffer[..bytes_read])
+        .expect("Failed to decode response")
+        .message
+    {
+        Some(server_message::Message::AddResponse(response)) => response.result,
+        _ => panic!("Expected AddResponse, but received a different message"),
+    }
+}
+
+/// The server side of `test_socket_activation`, run in a child process that
+/// inherits its listener the way systemd passes it. Does nothing otherwise.
+#[test]
+fn socket_activated_child() {
+    if env::var_os(CHILD_ENV).is_none() {
+        return;
+    }
+
+    let server = Server::from_systemd().expect("Failed to adopt listener");
+    // The sockets are only handed out once
+    assert!(systemd::listen_fds().unwrap().is_empty());
+    let (server, handle) = common::start(server);
+    // The parent kills this process once it got its answer
+    thread::sleep(Duration::from_secs(30));
+    server.stop();
+    handle.join().unwrap();
+}
+
+#[test]
+fn test_socket_activation() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let fd = listener.as_raw_fd();
+
+    // Like systemd: the socket becomes fd 3 and LISTEN_PID names the process
+    // that ends up running the server, which `exec` keeps equal to the shell's
+    let mut command = Command::new("sh");
+    command
+        .arg("-c")
+        .arg(r#"LISTEN_PID=$$ LISTEN_FDS=1 exec "$0" "$@""#)
+        .arg(env::current_exe().unwrap())
+        .args(["--exact", "socket_activated_child", "--nocapture"])
+        .env(CHILD_ENV, "1")
+        .stdout(Stdio::null());
+    // SAFETY: dup2 and fcntl are async-signal-safe
+    unsafe {
+        command.pre_exec(move || {
+            // dup2 onto itself would keep the close-on-exec flag
+            let result = if fd == 3 {
+                libc::fcntl(fd, libc::F_SETFD, 0)
+            } else {
+                libc::dup2(fd, 3)
+            };
+            if result == -1 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    let mut child = command.spawn().expect("Failed to start child");
+    // Only the child accepts from now on
+    drop(listener);
+
+    let mut stream = TcpStream::connect(addr).expect("Failed to connect");
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    let result = add(&mut stream, 40, 2);
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+    assert_eq!(result, 42);
+}
+
+#[test]
+fn test_not_socket_activated() {
+    let err = Server::from_systemd()
+        .err()
+        .expect("Server without inherited sockets should fail");
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+}
+
+#[test]
+fn test_listener_from_fd() {
+    let tcp = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = tcp.local_addr().unwrap();
+    let listener = Listener::from_fd(OwnedFd::from(tcp)).expect("Failed to adopt TCP socket");
+    assert!(matches!(listener, Listener::Tcp(_)));
+    assert_eq!(listener.local_addr().unwrap(), addr.to_string());
+
+    let path = env::temp_dir().join(format!("ert-adopted-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let unix = UnixListener::bind(&path).unwrap();
+    let listener = Listener::from_fd(OwnedFd::from(unix)).expect("Failed to adopt Unix socket");
+    assert_eq!(
+        listener.local_addr().unwrap(),
+        format!("unix:{}", path.display())
+    );
+    // The socket file belongs to whoever created it
+    drop(listener);
+    assert!(path.exists());
+    std::fs::remove_file(&path).unwrap();
+
+    // A connected socket is not a listener
+    #[cfg(target_os = "linux")]
+    {
+        let (a, _b) = std::os::unix::net::UnixStream::pair().unwrap();
+        assert!(Listener::from_fd(OwnedFd::from(a)).is_err());
+    }
+}
@@ -0,0 +1,163 @@
+#![cfg(feature = "tls")]
+
+use embedded_recruitment_task::{
+    client::Client,
+    server::Server,
+    tls::{TlsClientConfig, TlsConfig},
+};
+use rcgen::{
+    BasicConstraints, Certificate, CertificateParams, DnType, ExtendedKeyUsagePurpose, IsCa,
+    KeyPair,
+};
+use std::{
+    fs,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
+
+mod common;
+
+/// A throwaway CA plus server and client certificates it signed, as PEM files
+struct TestPki {
+    dir: PathBuf,
+}
+
+impl TestPki {
+    fn generate(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("ert-tls-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let ca_key = KeyPair::generate().unwrap();
+        let mut ca_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        ca_params
+            .distinguished_name
+            .push(DnType::CommonName, "Test CA");
+        let ca_cert = ca_params.self_signed(&ca_key).unwrap();
+        fs::write(dir.join("ca.pem"), ca_cert.pem()).unwrap();
+
+        let mut server_params = CertificateParams::new(vec!["localhost".to_string()]).unwrap();
+        server_params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ServerAuth];
+        write_leaf(&dir, "server", server_params, &ca_cert, &ca_key);
+
+        let mut client_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+        client_params
+            .distinguished_name
+            .push(DnType::CommonName, "device-1");
+        client_params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ClientAuth];
+        write_leaf(&dir, "client", client_params, &ca_cert, &ca_key);
+
+        TestPki { dir }
+    }
+
+    fn server_config(&self, require_client_cert: bool) -> TlsConfig {
+        TlsConfig {
+            cert_chain: self.dir.join("server.pem"),
+            private_key: self.dir.join("server.key"),
+            client_ca: require_client_cert.then(|| self.dir.join("ca.pem")),
+        }
+    }
+
+    fn client_config(&self, with_identity: bool) -> TlsClientConfig {
+        TlsClientConfig {
+            ca: self.dir.join("ca.pem"),
+            identity: with_identity
+                .then(|| (self.dir.join("client.pem"), self.dir.join("client.key"))),
+        }
+    }
+}
+
+impl Drop for TestPki {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn write_leaf(
+    dir: &Path,
+    name: &str,
+    params: CertificateParams,
+    ca_cert: &Certificate,
+    ca_key: &KeyPair,
+) {
+    let key = KeyPair::generate().unwrap();
+    let cert = params.signed_by(&key, ca_cert, ca_key).unwrap();
+    fs::write(dir.join(format!("{}.pem", name)), cert.pem()).unwrap();
+    fs::write(dir.join(format!("{}.key", name)), key.serialize_pem()).unwrap();
+}
+
+/// Client verifying the server as `localhost` against `config`
+fn tls_client(server: &Server, config: &TlsClientConfig) -> Client {
+    let addr: SocketAddr = server.address().parse().unwrap();
+    Client::new(addr)
+        .with_tls(config, "localhost")
+        .expect("Invalid TLS client configuration")
+}
+
+#[test]
+fn test_tls_echo_and_add() {
+    let pki = TestPki::generate("echo");
+    let (server, handle) = common::start(
+        Server::new_tls("localhost:0", &pki.server_config(false)).expect("Failed to start server"),
+    );
+
+    let mut client = tls_client(&server, &pki.client_config(false));
+    client.connect().expect("TLS handshake failed");
+    assert_eq!(client.echo("Hello over TLS").unwrap(), "Hello over TLS");
+    assert_eq!(client.add(2, 3).unwrap(), 5);
+
+    client.disconnect().expect("Failed to disconnect");
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_tls_rejects_untrusted_server() {
+    let pki = TestPki::generate("untrusted");
+    let other_pki = TestPki::generate("untrusted-other");
+    let (server, handle) = common::start(
+        Server::new_tls("localhost:0", &pki.server_config(false)).expect("Failed to start server"),
+    );
+
+    // Trusting a different CA must fail the handshake
+    let mut client = tls_client(&server, &other_pki.client_config(false));
+    assert!(client.connect().is_err());
+
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_mutual_tls() {
+    let pki = TestPki::generate("mtls");
+    let (server, handle) = common::start(
+        Server::new_tls("localhost:0", &pki.server_config(true)).expect("Failed to start server"),
+    );
+
+    // A client presenting a certificate signed by the trusted CA is served
+    let mut client = tls_client(&server, &pki.client_config(true));
+    client.connect().expect("TLS handshake failed");
+    assert_eq!(client.add(1, 1).unwrap(), 2);
+    client.disconnect().expect("Failed to disconnect");
+
+    // Without a certificate the server aborts the session. With TLS 1.3 the
+    // client only learns about it once it tries to use the connection.
+    let mut anonymous = tls_client(&server, &pki.client_config(false));
+    let served = anonymous.connect().and_then(|_| anonymous.add(1, 1));
+    assert!(
+        served.is_err(),
+        "Server answered a client without a certificate"
+    );
+
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
@@ -0,0 +1,120 @@
+use embedded_recruitment_task::{
+    message::{client_message, AddRequest, EchoMessage},
+    server::Server,
+};
+use std::{
+    io,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+use tracing_subscriber::fmt::format::FmtSpan;
+
+mod client;
+mod common;
+
+/// Collects everything the subscriber writes
+#[derive(Clone, Default)]
+struct Output(Arc<Mutex<Vec<u8>>>);
+
+impl io::Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Output {
+    fn lines(&self) -> Vec<String> {
+        String::from_utf8_lossy(&self.0.lock().unwrap())
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+#[test]
+fn test_connection_and_request_spans() {
+    let output = Output::default();
+    let writer = output.clone();
+    tracing::subscriber::set_global_default(
+        tracing_subscriber::fmt()
+            .with_writer(move || writer.clone())
+            .with_span_events(FmtSpan::CLOSE)
+            .finish(),
+    )
+    .expect("Failed to install subscriber");
+
+    let (server, handle) =
+        common::start(Server::new("localhost:0").expect("Failed to start server"));
+
+    let addr: SocketAddr = server.address().parse().unwrap();
+    let mut clients: Vec<_> = (0..2)
+        .map(|_| {
+            let mut client = client::Client::new(&addr.ip().to_string(), addr.port().into(), 1000);
+            client.connect().expect("Failed to connect to the server");
+            client
+        })
+        .collect();
+    // Give the accept loop time to number the connections in order
+    thread::sleep(Duration::from_millis(100));
+
+    clients[0]
+        .send(client_message::Message::AddRequest(AddRequest {
+            a: 1,
+            b: 2,
+        }))
+        .expect("Failed to send message");
+    clients[0].receive().expect("Failed to receive");
+    clients[1]
+        .send(client_message::Message::EchoMessage(EchoMessage {
+            content: "traced".to_string(),
+        }))
+        .expect("Failed to send message");
+    clients[1].receive().expect("Failed to receive");
+    for client in &mut clients {
+        client.disconnect().expect("Failed to disconnect");
+    }
+    thread::sleep(Duration::from_millis(100));
+
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+
+    let lines = output.lines();
+    let closed_request = |id: u64, message_type: &str| {
+        lines.iter().any(|line| {
+            line.contains(&format!("connection{{id={} peer=", id))
+                && line.contains("request{size=")
+                && line.contains(&format!("message_type=\"{}\"", message_type))
+                && line.contains("latency_us=")
+                && line.contains("outcome=\"ok\"")
+                && line.contains("close")
+        })
+    };
+    assert!(
+        closed_request(1, "add_request"),
+        "No add_request span on connection 1 in:\n{}",
+        lines.join("\n")
+    );
+    assert!(
+        closed_request(2, "echo_message"),
+        "No echo_message span on connection 2 in:\n{}",
+        lines.join("\n")
+    );
+    assert!(lines
+        .iter()
+        .any(|line| line.contains("connection{id=2") && line.contains("disconnected")));
+    // Handler events land in the connection's span
+    assert!(lines
+        .iter()
+        .any(|line| line.contains("connection{id=2")
+            && line.contains("Received EchoMessage: traced")));
+}
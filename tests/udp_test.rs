@@ -0,0 +1,171 @@
+use embedded_recruitment_task::{
+    message::{
+        client_message, server_message, AddRequest, ClientMessage, EchoMessage, ErrorCode,
+        ServerMessage,
+    },
+    udp::{UdpConfig, UdpServer},
+};
+use prost::Message;
+use std::{io::ErrorKind, net::UdpSocket, time::Duration};
+
+mod common;
+
+fn create_server(config: UdpConfig) -> UdpServer {
+    UdpServer::new("127.0.0.1:0", config).expect("Failed to start server")
+}
+
+fn connect(server: &UdpServer) -> UdpSocket {
+    let socket = UdpSocket::bind("127.0.0.1:0").expect("Failed to bind client socket");
+    socket.connect(server.address()).expect("Failed to connect");
+    socket
+        .set_read_timeout(Some(Duration::from_millis(500)))
+        .unwrap();
+    socket
+}
+
+fn send(socket: &UdpSocket, message: client_message::Message, request_id: u64) {
+    let request = ClientMessage {
+        message: Some(message),
+        request_id,
+    };
+    socket
+        .send(&request.encode_to_vec())
+        .expect("Failed to send datagram");
+}
+
+fn receive(socket: &UdpSocket) -> std::io::Result<ServerMessage> {
+    let mut buffer = vec![0u8; 65536];
+    let bytes_read = socket.recv(&mut buffer)?;
+    Ok(ServerMessage::decode(&buffer[..bytes_read]).expect("Failed to decode response"))
+}
+
+fn echo(content: &str) -> client_message::Message {
+    client_message::Message::EchoMessage(EchoMessage {
+        content: content.to_string(),
+    })
+}
+
+fn echoed_content(response: ServerMessage) -> String {
+    match response.message {
+        Some(server_message::Message::EchoMessage(echo)) => echo.content,
+        _ => panic!("Expected EchoMessage, but received a different message"),
+    }
+}
+
+#[test]
+fn test_udp_echo_and_add() {
+    let (server, handle) = common::start(create_server(UdpConfig::default()));
+
+    let socket = connect(&server);
+
+    send(&socket, echo("Hello, World!"), 1);
+    let response = receive(&socket).expect("Failed to receive EchoMessage");
+    assert_eq!(response.request_id, 1);
+    assert_eq!(echoed_content(response), "Hello, World!");
+
+    send(
+        &socket,
+        client_message::Message::AddRequest(AddRequest { a: 10, b: 20 }),
+        2,
+    );
+    let response = receive(&socket).expect("Failed to receive AddResponse");
+    assert_eq!(response.request_id, 2);
+    match response.message {
+        Some(server_message::Message::AddResponse(add)) => assert_eq!(add.result, 30),
+        _ => panic!("Expected AddResponse, but received a different message"),
+    }
+
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_udp_duplicate_request_is_suppressed() {
+    let (server, handle) = common::start(create_server(UdpConfig::default()));
+
+    let socket = connect(&server);
+
+    send(&socket, echo("first"), 7);
+    assert_eq!(echoed_content(receive(&socket).unwrap()), "first");
+
+    // A retransmission with the same ID gets the original answer back
+    send(&socket, echo("second"), 7);
+    assert_eq!(echoed_content(receive(&socket).unwrap()), "first");
+
+    // Without a request ID every datagram is handled on its own
+    send(&socket, echo("third"), 0);
+    assert_eq!(echoed_content(receive(&socket).unwrap()), "third");
+    send(&socket, echo("fourth"), 0);
+    assert_eq!(echoed_content(receive(&socket).unwrap()), "fourth");
+
+    // The same ID from another peer is a different request
+    let other = connect(&server);
+    send(&other, echo("other"), 7);
+    assert_eq!(echoed_content(receive(&other).unwrap()), "other");
+
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_udp_oversized_datagram_is_dropped() {
+    let (server, handle) = common::start(create_server(UdpConfig {
+        max_datagram_size: 512,
+        ..Default::default()
+    }));
+
+    let socket = connect(&server);
+
+    send(&socket, echo(&"s".repeat(1024)), 1);
+    let err = receive(&socket).expect_err("Oversized datagram should not be answered");
+    assert!(matches!(
+        err.kind(),
+        ErrorKind::WouldBlock | ErrorKind::TimedOut
+    ));
+
+    // The server keeps serving normal-sized requests
+    send(&socket, echo("small"), 2);
+    assert_eq!(echoed_content(receive(&socket).unwrap()), "small");
+
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_udp_add_overflow() {
+    let (server, handle) = common::start(create_server(UdpConfig::default()));
+
+    let socket = connect(&server);
+    send(
+        &socket,
+        client_message::Message::AddRequest(AddRequest { a: i32::MAX, b: 1 }),
+        1,
+    );
+    match receive(&socket)
+        .expect("No response to the overflowing AddRequest")
+        .message
+    {
+        Some(server_message::Message::Error(error)) => {
+            assert_eq!(error.code(), ErrorCode::InvalidArgument);
+            assert_eq!(error.field, "add_request");
+        }
+        other => panic!("Expected an InvalidArgument error, got {:?}", other),
+    }
+
+    // The server survives and keeps answering
+    send(&socket, echo("still there"), 2);
+    let response = receive(&socket).expect("Server stopped answering");
+    assert_eq!(echoed_content(response), "still there");
+
+    server.stop();
+    handle.join().expect("Server thread panicked");
+}
@@ -0,0 +1,165 @@
+#![cfg(unix)]
+
+use embedded_recruitment_task::{
+    listener::{Listener, PeerAddr, UnixSocketConfig},
+    message::{
+        client_message, server_message, AddRequest, ClientMessage, EchoMessage, ServerMessage,
+    },
+    server::Server,
+};
+use prost::Message;
+use std::{
+    fs,
+    io::{Read, Write},
+    os::unix::{
+        fs::PermissionsExt,
+        net::{UnixListener, UnixStream},
+    },
+    path::PathBuf,
+};
+
+mod common;
+
+fn socket_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("ert-{}-{}.sock", name, std::process::id()))
+}
+
+fn exchange(stream: &mut UnixStream, message: client_message::Message) -> ServerMessage {
+    let request = ClientMessage {
+        message: Some(message),
+        ..Default::default()
+    };
+    stream
+        .write_all(&request.encode_to_vec())
+        .expect("Failed to send message");
+
+    let mut buffer = vec![0u8; 65536];
+    let bytes_read = stream.read(&mut buffer).expect("Failed to read response");
+    ServerMessage::decode(&buffer[..bytes_read]).expect("Failed to decode response")
+}
+
+#[test]
+fn test_unix_socket_echo_and_add() {
+    let path = socket_path("echo");
+    let (server, handle) = common::start(
+        Server::bind_unix(&path, &UnixSocketConfig::default()).expect("Failed to start server"),
+    );
+    assert_eq!(server.address(), format!("unix:{}", path.display()));
+
+    let mut stream = UnixStream::connect(&path).expect("Failed to connect to the server");
+
+    let echo = EchoMessage {
+        content: "Hello over a Unix socket".to_string(),
+    };
+    match exchange(
+        &mut stream,
+        client_message::Message::EchoMessage(echo.clone()),
+    )
+    .message
+    {
+        Some(server_message::Message::EchoMessage(response)) => {
+            assert_eq!(response.content, echo.content)
+        }
+        _ => panic!("Expected EchoMessage, but received a different message"),
+    }
+
+    let add = AddRequest { a: 7, b: 35 };
+    match exchange(&mut stream, client_message::Message::AddRequest(add)).message {
+        Some(server_message::Message::AddResponse(response)) => assert_eq!(response.result, 42),
+        _ => panic!("Expected AddResponse, but received a different message"),
+    }
+
+    drop(stream);
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+
+    drop(server);
+    assert!(!path.exists(), "Socket file was not removed on shutdown");
+}
+
+#[test]
+fn test_unix_socket_stale_file_is_removed() {
+    let path = socket_path("stale");
+    let _ = fs::remove_file(&path);
+
+    // Leave a socket file behind without anyone listening on it
+    drop(UnixListener::bind(&path).expect("Failed to create stale socket"));
+    assert!(path.exists());
+
+    let server =
+        Server::bind_unix(&path, &UnixSocketConfig::default()).expect("Failed to reuse stale path");
+    assert!(UnixStream::connect(&path).is_ok());
+    drop(server);
+}
+
+#[test]
+fn test_unix_socket_live_file_is_not_replaced() {
+    let path = socket_path("live");
+    let _first =
+        Server::bind_unix(&path, &UnixSocketConfig::default()).expect("Failed to start server");
+
+    let second = Server::bind_unix(&path, &UnixSocketConfig::default());
+    assert_eq!(
+        second.err().map(|e| e.kind()),
+        Some(std::io::ErrorKind::AddrInUse)
+    );
+}
+
+#[test]
+fn test_unix_socket_permissions() {
+    let path = socket_path("mode");
+    let config = UnixSocketConfig {
+        mode: Some(0o600),
+        ..Default::default()
+    };
+    let (server, handle) =
+        common::start(Server::bind_unix(&path, &config).expect("Failed to start server"));
+    assert_eq!(server.address(), format!("unix:{}", path.display()));
+
+    let mode = fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+
+    // The socket bound elsewhere serves at its path
+    let mut stream = UnixStream::connect(&path).expect("Failed to connect");
+    let add = AddRequest { a: 1, b: 1 };
+    match exchange(&mut stream, client_message::Message::AddRequest(add)).message {
+        Some(server_message::Message::AddResponse(response)) => assert_eq!(response.result, 2),
+        _ => panic!("Expected AddResponse, but received a different message"),
+    }
+
+    // and still refuses to replace a live socket
+    let second = Server::bind_unix(&path, &config);
+    assert_eq!(
+        second.err().map(|e| e.kind()),
+        Some(std::io::ErrorKind::AddrInUse)
+    );
+
+    drop(stream);
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+    drop(server);
+    assert!(!path.exists());
+}
+
+#[test]
+fn test_unix_socket_peer_credentials() {
+    let path = socket_path("creds");
+    let listener =
+        Listener::bind_unix(&path, &UnixSocketConfig::default()).expect("Failed to bind");
+
+    let _client = UnixStream::connect(&path).expect("Failed to connect");
+    let (_stream, info) = listener.accept().expect("Failed to accept");
+
+    assert!(matches!(info.peer, PeerAddr::Unix(None)));
+    let credentials = info.credentials.expect("Missing peer credentials");
+    assert_eq!(credentials.uid, unsafe { libc::getuid() });
+    assert_eq!(credentials.gid, unsafe { libc::getgid() });
+    #[cfg(target_os = "linux")]
+    assert_eq!(credentials.pid, Some(std::process::id()));
+}
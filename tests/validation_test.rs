@@ -0,0 +1,137 @@
+use embedded_recruitment_task::{
+    handler::ClientMessageWrapper,
+    message::{
+        client_message, server_message, AddRequest, ClientMessage, EchoMessage, ErrorCode,
+        ServerMessage,
+    },
+    server::Server,
+    validation::{CharClass, ValidationRules},
+};
+use std::{net::SocketAddr, sync::Arc, thread::JoinHandle};
+
+mod client;
+mod common;
+
+fn start_server(rules: ValidationRules) -> (Arc<Server>, JoinHandle<()>) {
+    common::start(
+        Server::new("localhost:0")
+            .expect("Failed to start server")
+            .with_validation(rules),
+    )
+}
+
+fn connect(server: &Server) -> client::Client {
+    let addr: SocketAddr = server.address().parse().unwrap();
+    let mut client = client::Client::new(&addr.ip().to_string(), addr.port().into(), 1000);
+    client.connect().expect("Failed to connect to the server");
+    client
+}
+
+fn request(client: &mut client::Client, message: client_message::Message) -> ServerMessage {
+    client.send(message).expect("Failed to send message");
+    client.receive().expect("Failed to receive")
+}
+
+fn echo(client: &mut client::Client, content: &str) -> ServerMessage {
+    request(
+        client,
+        client_message::Message::EchoMessage(EchoMessage {
+            content: content.to_string(),
+        }),
+    )
+}
+
+fn add(client: &mut client::Client, a: i32, b: i32) -> ServerMessage {
+    request(
+        client,
+        client_message::Message::AddRequest(AddRequest { a, b }),
+    )
+}
+
+fn assert_invalid(response: ServerMessage, field: &str) {
+    match response.message {
+        Some(server_message::Message::Error(error)) => {
+            assert_eq!(error.code(), ErrorCode::InvalidArgument);
+            assert_eq!(error.field, field);
+            assert!(
+                error.message.starts_with(field),
+                "Unexpected message {:?}",
+                error.message
+            );
+        }
+        other => panic!("Expected an InvalidArgument error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_violations_name_the_field() {
+    let (server, handle) = start_server(ValidationRules {
+        max_content_length: Some(8),
+        content_chars: CharClass::AsciiPrintable,
+        add_operand_range: Some(-100..=100),
+    });
+    let mut client = connect(&server);
+
+    assert!(matches!(
+        echo(&mut client, "hello").message,
+        Some(server_message::Message::EchoMessage(_))
+    ));
+    assert_invalid(echo(&mut client, "far too long"), "echo_message.content");
+    assert_invalid(echo(&mut client, "tab\there"), "echo_message.content");
+    assert_invalid(echo(&mut client, "héllo"), "echo_message.content");
+
+    match add(&mut client, -100, 100).message {
+        Some(server_message::Message::AddResponse(response)) => assert_eq!(response.result, 0),
+        other => panic!("Expected an AddResponse, got {:?}", other),
+    }
+    assert_invalid(add(&mut client, 101, 1), "add_request.a");
+    assert_invalid(add(&mut client, 1, -101), "add_request.b");
+
+    // The connection stays usable after a rejected request
+    assert!(matches!(
+        add(&mut client, 2, 3).message,
+        Some(server_message::Message::AddResponse(_))
+    ));
+
+    client.disconnect().expect("Failed to disconnect");
+    server.stop();
+    handle.join().expect("Server thread panicked");
+}
+
+#[test]
+fn test_default_rules_accept_everything_answerable() {
+    let rules = ValidationRules::default();
+    let (server, handle) = start_server(rules);
+    let mut client = connect(&server);
+
+    let content = "line one\nline two \u{0}".repeat(100);
+    match echo(&mut client, &content).message {
+        Some(server_message::Message::EchoMessage(echo)) => assert_eq!(echo.content, content),
+        other => panic!("Expected an EchoMessage, got {:?}", other),
+    }
+    match add(&mut client, i32::MAX, 0).message {
+        Some(server_message::Message::AddResponse(response)) => {
+            assert_eq!(response.result, i32::MAX)
+        }
+        other => panic!("Expected an AddResponse, got {:?}", other),
+    }
+    // Except sums that do not fit the response
+    assert_invalid(add(&mut client, i32::MAX, 1), "add_request");
+    assert_eq!(
+        ValidationRules::default()
+            .validate(&ClientMessageWrapper::from(ClientMessage {
+                message: Some(client_message::Message::AddRequest(AddRequest {
+                    a: i32::MIN,
+                    b: -1,
+                })),
+                ..Default::default()
+            }))
+            .unwrap_err()
+            .field,
+        "add_request"
+    );
+
+    client.disconnect().expect("Failed to disconnect");
+    server.stop();
+    handle.join().expect("Server thread panicked");
+}
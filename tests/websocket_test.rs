@@ -0,0 +1,130 @@
+#![cfg(feature = "websocket")]
+
+use embedded_recruitment_task::{
+    message::{
+        client_message, server_message, AddRequest, ClientMessage, EchoMessage, ServerMessage,
+    },
+    server::Server,
+};
+use prost::Message;
+use std::net::TcpStream;
+use tungstenite::{stream::MaybeTlsStream, Message as WsMessage, WebSocket};
+
+mod common;
+
+type Socket = WebSocket<MaybeTlsStream<TcpStream>>;
+
+fn create_server() -> Server {
+    Server::new_websocket("localhost:0").expect("Failed to start server")
+}
+
+fn connect(server: &Server) -> Socket {
+    let (socket, _) =
+        tungstenite::connect(format!("{}/", server.address())).expect("Failed to connect");
+    socket
+}
+
+fn exchange(socket: &mut Socket, message: client_message::Message) -> ServerMessage {
+    let request = ClientMessage {
+        message: Some(message),
+        ..Default::default()
+    };
+    socket
+        .send(WsMessage::Binary(request.encode_to_vec()))
+        .expect("Failed to send message");
+
+    match socket.read().expect("Failed to read response") {
+        WsMessage::Binary(data) => ServerMessage::decode(&data[..]).expect("Failed to decode"),
+        other => panic!("Expected a binary message, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_websocket_echo_and_add() {
+    let (server, handle) = common::start(create_server());
+    assert!(server.address().starts_with("ws://"));
+
+    let mut socket = connect(&server);
+
+    let echo = EchoMessage {
+        content: "Hello from the dashboard".to_string(),
+    };
+    match exchange(
+        &mut socket,
+        client_message::Message::EchoMessage(echo.clone()),
+    )
+    .message
+    {
+        Some(server_message::Message::EchoMessage(response)) => {
+            assert_eq!(response.content, echo.content)
+        }
+        _ => panic!("Expected EchoMessage, but received a different message"),
+    }
+
+    let add = AddRequest { a: 20, b: 22 };
+    match exchange(&mut socket, client_message::Message::AddRequest(add)).message {
+        Some(server_message::Message::AddResponse(response)) => assert_eq!(response.result, 42),
+        _ => panic!("Expected AddResponse, but received a different message"),
+    }
+
+    socket.close(None).expect("Failed to close WebSocket");
+    while socket.read().is_ok() {}
+
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_websocket_ignores_text_messages() {
+    let (server, handle) = common::start(create_server());
+
+    let mut socket = connect(&server);
+    socket
+        .send(WsMessage::Text("not protobuf".to_string()))
+        .expect("Failed to send text message");
+
+    // The connection keeps working for binary frames afterwards
+    let add = AddRequest { a: 1, b: 2 };
+    match exchange(&mut socket, client_message::Message::AddRequest(add)).message {
+        Some(server_message::Message::AddResponse(response)) => assert_eq!(response.result, 3),
+        _ => panic!("Expected AddResponse, but received a different message"),
+    }
+
+    socket.close(None).expect("Failed to close WebSocket");
+    while socket.read().is_ok() {}
+
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_websocket_ignores_empty_messages() {
+    let (server, handle) = common::start(create_server());
+
+    let mut socket = connect(&server);
+    socket
+        .send(WsMessage::Binary(Vec::new()))
+        .expect("Failed to send empty message");
+
+    // An empty message does not end the connection
+    let add = AddRequest { a: 4, b: 5 };
+    match exchange(&mut socket, client_message::Message::AddRequest(add)).message {
+        Some(server_message::Message::AddResponse(response)) => assert_eq!(response.result, 9),
+        _ => panic!("Expected AddResponse, but received a different message"),
+    }
+
+    socket.close(None).expect("Failed to close WebSocket");
+    while socket.read().is_ok() {}
+
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}